@@ -1,19 +1,84 @@
 mod nba;
 
 use nba::db::{SaveToDB, SaveToDataframe};
-use nba::endpoints::{BoxScoreDefensive, PlayByPlayV2, CommonAllPlayers};
+use nba::endpoints::{AsyncNBAEndpoint, BoxScoreDefensive, NBAEndpoint, PlayByPlayV2, CommonAllPlayers, GenericEndpoint};
 use clap::{Parser, Subcommand};
+use std::{collections::HashMap, path::Path, sync::Arc, time::Duration};
+use chrono::NaiveDate;
+use polars::prelude::DataFrame;
+use serde_json::Value;
 
-use crate::nba::endpoints::VidForPlay;
+use crate::nba::endpoints::{ScoreboardV2, VidForPlay};
+use crate::nba::transport::{Transport, UreqTransport, NBA_STATS_BASE_URL, NBA_CDN_BASE_URL};
+use crate::nba::cache::{CachedTransport, DEFAULT_CACHE_DIR};
+use crate::nba::throttle::{ThrottledTransport, DEFAULT_MAX_RETRIES, DEFAULT_REQUESTS_PER_SECOND};
+use crate::nba::error::{NbaError, NbaResult};
+use crate::nba::filter::EventFilter;
+use crate::nba::params::{EndPeriod, GameDate, Period, Season, SeasonType, StartPeriod};
+use crate::nba::sync::sync_season;
+use crate::nba::watch::{follow_playbyplay, watch_boxscores, watch_scoreboard, DEFAULT_WATCH_INTERVAL};
+use crate::nba::live_data::{live_events_from_json, live_events_table, playbyplay_url};
+use crate::nba::events::PlayByPlayEvent;
+use crate::nba::warehouse::{Warehouse, DEFAULT_WAREHOUSE_PATH};
+use crate::nba::output::{merge_table, Output, OutputFormat, OutputTable};
+use crate::nba::export::{export_table, ExportFormat, ExportQuery, DEFAULT_EXPORT_DIR};
+use crate::nba::alerts::{parse_sink, read_rules, AlertEngine, AlertRule};
 
 //TODOs
 // Ability to lookup consts like team id, player id,
 // sync constants 
-// Call any endpoint, cmd to build parameters for you
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct NBACli {
+    /// Point stats requests at another server, e.g. a local stand-in
+    #[clap(long, global = true, default_value = NBA_STATS_BASE_URL)]
+    stats_base_url: String,
+
+    /// Point live data requests at another server
+    #[clap(long, global = true, default_value = NBA_CDN_BASE_URL)]
+    cdn_base_url: String,
+
+    /// Send every request through this http proxy
+    #[clap(long, global = true)]
+    proxy: Option<String>,
+
+    /// Only serve responses from the local cache, fail on anything not cached
+    #[clap(long, global = true)]
+    offline: bool,
+
+    /// Where cached responses are kept
+    #[clap(long, global = true, default_value = DEFAULT_CACHE_DIR)]
+    cache_dir: String,
+
+    /// Upper bound on requests sent to stats.nba.com
+    #[clap(long, global = true, default_value_t = DEFAULT_REQUESTS_PER_SECOND)]
+    requests_per_second: f64,
+
+    /// How often a timed out, rate limited or 5xx request is retried
+    #[clap(long, global = true, default_value_t = DEFAULT_MAX_RETRIES)]
+    max_retries: u32,
+
+    /// The sqlite file every fetched dataset is stored in
+    #[clap(long, global = true, default_value = DEFAULT_WAREHOUSE_PATH)]
+    warehouse: String,
+
+    /// Fetch everything again, ignoring cached responses and stored data
+    #[clap(long, global = true, conflicts_with_all = &["offline", "max-age"])]
+    refresh: bool,
+
+    /// Don't fetch data stored less than this long ago, e.g. 90s, 30m, 12h or 7d
+    #[clap(long, global = true, parse(try_from_str = parse_duration))]
+    max_age: Option<Duration>,
+
+    /// How results are printed: table, csv, json, ndjson or markdown
+    #[clap(long, global = true)]
+    format: Option<OutputFormat>,
+
+    /// Write results to this file instead of stdout
+    #[clap(short, long, global = true)]
+    output: Option<String>,
+
     #[clap(subcommand)]
     cmd: Commands
 }
@@ -30,12 +95,42 @@ enum Commands {
         #[clap(short, long)]
         endpoint: Option<String>,
     },
-    Scoreboard,
+    /// Today's games from the live feed, or the games of another day
+    Scoreboard {
+        /// Day to show, e.g. 2022-05-29
+        #[clap(long, conflicts_with_all = &["yesterday", "tomorrow"])]
+        date: Option<NaiveDate>,
+
+        #[clap(long, conflicts_with = "tomorrow")]
+        yesterday: bool,
+
+        #[clap(long)]
+        tomorrow: bool,
+
+        /// Keep polling the live feed and print what changed until every game is final
+        #[clap(long, conflicts_with_all = &["date", "yesterday", "tomorrow"])]
+        watch: bool,
+
+        /// Time between polls with --watch, e.g. 15s or 1m
+        #[clap(long, parse(try_from_str = parse_duration), default_value = DEFAULT_WATCH_INTERVAL)]
+        interval: Duration,
+    },
+    /// Live box score of each game, or the defensive one with --defensive
     Boxscore {
-        game_id: String,
+        /// One or more games, fetched concurrently
+        #[clap(required = true)]
+        game_ids: Vec<String>,
 
         #[clap(short, long)]
         defensive: bool,
+
+        /// Keep polling and print a game's box score again whenever it changes, until every game is final
+        #[clap(long, conflicts_with = "defensive")]
+        watch: bool,
+
+        /// Time between polls with --watch, e.g. 15s or 1m
+        #[clap(long, parse(try_from_str = parse_duration), default_value = DEFAULT_WATCH_INTERVAL)]
+        interval: Duration,
     },
     Playbyplay {
         game_id: String,
@@ -43,79 +138,312 @@ enum Commands {
         #[clap(short, long, required = false)]
         player_id: Option<String>,
 
-        #[clap(short, long, required = false)]
-        keyword: Option<String>,
+        /// Event kind to keep, e.g. block, steal, 3pt, missed-shot or a shot
+        /// type like running-pull-up-jump-shot
+        #[clap(short = 'k', long, alias = "keyword", required = false)]
+        kind: Option<String>,
 
+        /// Conditions every event has to meet, e.g.
+        /// "event=turnover and team=BOS and period>=4 and clock<=5:00 and margin<=5"
         #[clap(short, long)]
-        save_videos: bool
+        filter: Option<String>,
+
+        #[clap(short, long)]
+        save_videos: bool,
+
+        /// Print every parsed event, one json object per line unless --format says otherwise
+        #[clap(long)]
+        events: bool,
+
+        /// Clips downloaded at the same time with --save-videos
+        #[clap(long, default_value_t = 4)]
+        video_workers: usize,
+
+        /// Read the CDN's live action feed instead of playbyplayv2, which lags behind live games
+        #[clap(long, conflicts_with = "save-videos")]
+        live: bool,
+
+        /// With --live, keep polling and print new actions as they come until the game ends
+        #[clap(long, requires = "live")]
+        follow: bool,
+
+        /// Time between polls with --follow, e.g. 5s
+        #[clap(long, parse(try_from_str = parse_duration), default_value = DEFAULT_WATCH_INTERVAL)]
+        interval: Duration,
     },
     Vidforplay {
         game_id: String,
 
-        game_event_id: String,
+        game_event_id: i64,
+    },
+    Call {
+        endpoint: String,
+
+        #[clap(short, long = "param", parse(try_from_str = parse_key_val))]
+        params: Vec<(String, String)>,
     },
     Savestaticdata,
+    /// Fetch and store the defensive box score and play by play of every
+    /// game passed, all games are requested concurrently
+    Savegames {
+        game_ids: Vec<String>,
+    },
+    /// Ingest many games at once
+    Sync {
+        #[clap(subcommand)]
+        target: SyncTarget,
+    },
+    /// Write a stored table to Parquet or Arrow IPC files partitioned by
+    /// season and game date
+    Export {
+        /// Warehouse table, e.g. playbyplay, playerstats or teamstats
+        table: String,
+
+        /// Conditions on play by play events, same syntax as playbyplay --filter
+        #[clap(short, long)]
+        filter: Option<String>,
+
+        /// Only games of this season, e.g. 2021-22
+        #[clap(long)]
+        season: Option<String>,
+
+        /// Only these games
+        #[clap(short, long = "game")]
+        game_ids: Vec<String>,
+
+        /// Only rows about these players
+        #[clap(short, long = "player")]
+        player_ids: Vec<i64>,
+
+        /// Only rows about these teams, ids or abbreviations
+        #[clap(short, long = "team")]
+        teams: Vec<String>,
+
+        /// parquet or ipc
+        #[clap(long = "to", default_value = "parquet")]
+        export_format: ExportFormat,
+
+        /// Directory the partitioned files are written under
+        #[clap(long, default_value = DEFAULT_EXPORT_DIR)]
+        dir: String,
+    },
+    /// Watch the live games and raise an alert, once per game, when a rule
+    /// holds, e.g. "clutch: period>=4 and clock<=3:00 and margin<=5"
+    Siren {
+        #[clap(short, long = "rule")]
+        rules: Vec<AlertRule>,
+
+        /// File with one rule per line, # starts a comment
+        #[clap(long = "rules")]
+        rules_file: Option<String>,
+
+        /// stdout, exec:<shell command> or webhook:<url>, may be repeated
+        #[clap(long = "sink", default_value = "stdout")]
+        sinks: Vec<String>,
+
+        /// Time between polls of the live scoreboard and play by play, e.g. 15s or 1m
+        #[clap(long, parse(try_from_str = parse_duration), default_value = DEFAULT_WATCH_INTERVAL)]
+        interval: Duration,
+    },
     Test,
 }
 
-fn fetch_endpoint(endpoint: &str) {
-    let endpoint_metadata = nba::live_data::get_endpoint_metadata().unwrap();
-    let metadata = endpoint_metadata.get(endpoint);
-    if metadata.is_none() {
-        println!("No info found for {}", endpoint);
-        return;
+#[derive(Debug, Subcommand)]
+enum SyncTarget {
+    /// Store the summary, box score and play by play of every game of a
+    /// season, resuming from the last run's checkpoint
+    Season {
+        /// e.g. 2021-22
+        season: String,
+
+        /// regular, playoffs, playin, preseason or allstar
+        #[clap(long, default_value = "regular")]
+        season_type: SeasonType,
+    },
+}
+
+fn parse_key_val(param: &str) -> Result<(String, String), String> {
+    match param.split_once('=') {
+        Some((key, val)) => Ok((key.to_string(), val.to_string())),
+        None => Err(format!("expected Key=Value, got {}", param)),
     }
-    let endpoint_params = metadata.unwrap().get("params").unwrap();
-    for (param_name, val) in endpoint_params.as_object().unwrap() {
-        println!("{}: {}", param_name, val.as_str().unwrap());
+}
+
+fn parse_duration(duration: &str) -> Result<Duration, String> {
+    let (number, unit_seconds) = match duration.char_indices().last() {
+        Some((pos, 's')) => (&duration[..pos], 1),
+        Some((pos, 'm')) => (&duration[..pos], 60),
+        Some((pos, 'h')) => (&duration[..pos], 60 * 60),
+        Some((pos, 'd')) => (&duration[..pos], 24 * 60 * 60),
+        _ => (duration, 1),
+    };
+    number.parse::<u64>().ok()
+        .and_then(|n| n.checked_mul(unit_seconds))
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("expected a number of seconds or e.g. 30m, 12h, 7d, got {}", duration))
+}
+
+fn fetch_endpoint(endpoint: &str) -> NbaResult<OutputTable> {
+    let endpoint_metadata = nba::live_data::get_endpoint_metadata()?;
+    let endpoint_params = endpoint_metadata[endpoint]["params"].as_object()
+        .ok_or_else(|| NbaError::InvalidParam(format!("No info found for {}", endpoint)))?;
+    let mut params_table = OutputTable::new("Params", &["param", "default"]);
+    for (param_name, val) in endpoint_params {
+        params_table.rows.push(vec![param_name.as_str().into(), val.as_str().unwrap_or("").into()]);
     }
+    Ok(params_table)
+}
+
+// Result sets sorted by name so the output doesn't depend on HashMap order
+fn dataframe_tables(frames: HashMap<String, DataFrame>) -> Vec<OutputTable> {
+    let mut frames: Vec<(String, DataFrame)> = frames.into_iter().collect();
+    frames.sort_by(|(a, _), (b, _)| a.cmp(b));
+    frames.iter().map(|(name, df)| OutputTable::from_dataframe(name, df)).collect()
 }
 
 
 fn main() {
     let args = NBACli::parse();
+    if let Err(e) = run(args) {
+        eprintln!("error: {}", e);
+        std::process::exit(e.exit_code());
+    }
+}
+
+fn run(args: NBACli) -> NbaResult<()> {
+    let ureq_transport = UreqTransport::new(&args.stats_base_url, &args.cdn_base_url, args.proxy.as_deref())?;
+    let throttled_transport = ThrottledTransport::new(Arc::new(ureq_transport), args.requests_per_second, args.max_retries)?;
+    let transport: Arc<dyn Transport> = Arc::new(
+        CachedTransport::new(Arc::new(throttled_transport), &args.cache_dir, args.offline, args.refresh)?
+    );
+    let warehouse = Warehouse::open(&args.warehouse, args.max_age)?;
+    let output = Output::new(args.format.unwrap_or(OutputFormat::Table), args.output.as_deref());
     match args.cmd {
         Commands::Lookup { endpoint, player_name, team_name } => {
             match (endpoint, player_name, team_name) {
                 (Some(e), _, _) => {
-                    fetch_endpoint(&e);
+                    output.write(&[fetch_endpoint(&e)?])?;
                 },
                 (_, Some(p), _) => {
                     let all_p_frames = CommonAllPlayers::new(
                         Default::default(),
                         Default::default(),
-                    );
-                    all_p_frames.save_to_db_file().unwrap();
-                    let res = all_p_frames.search_table("commonallplayers", vec!["display_first_last", "person_id"], vec!["display_first_last"], &p).unwrap();
-                    println!("{:?}", res);
+                        Arc::clone(&transport),
+                        warehouse.clone(),
+                    )?;
+                    all_p_frames.save_to_db_file()?;
+                    let mut players_table = OutputTable::new("Players", &["person_id", "display_first_last"]);
+                    for player in all_p_frames.search_players(&p)? {
+                        players_table.rows.push(vec![player.person_id.into(), player.display_first_last.into()]);
+                    }
+                    output.write(&[players_table])?;
                 },
                 (_, _, Some(t)) => {
                     let all_p_frames = CommonAllPlayers::new(
                         Default::default(),
                         Default::default(),
-                    );
-                    all_p_frames.save_to_db_file().unwrap();
-                    let res = all_p_frames.search_table("commonallplayers", vec!["team_id", "team_name"], vec!["team_city","team_name","team_abbreviation","team_code"], &t).unwrap();
-                    if res.len() > 0 {
-                        println!("{:?}", res.get(0).unwrap());
+                        Arc::clone(&transport),
+                        warehouse.clone(),
+                    )?;
+                    all_p_frames.save_to_db_file()?;
+                    let mut teams_table = OutputTable::new("Teams", &["team_id", "team_name"]);
+                    if let Some(team) = all_p_frames.search_teams(&t)?.first() {
+                        teams_table.rows.push(vec![team.team_id.into(), team.team_name.clone().map_or(Value::Null, Value::from)]);
                     }
-                    
+                    output.write(&[teams_table])?;
                 },
-                (_, _, _) => println!("unsupported args")
+                (None, None, None) => return Err(NbaError::InvalidParam("lookup needs --endpoint, --player-name or --team-name".to_string())),
 
             }
             
         }
+        Commands::Call { endpoint, params } => {
+            let generic_endpoint = GenericEndpoint::new(&endpoint, params, Arc::clone(&transport), warehouse.clone())?;
+            generic_endpoint.save_to_db_file()?;
+            let endpoint_frames = generic_endpoint.load_dataframes()?;
+            output.write(&dataframe_tables(endpoint_frames))?;
+        }
         Commands::Savestaticdata => {
             let player_info = CommonAllPlayers::new(
                     Default::default(),
                     Default::default(),
-            );
-            player_info.save_to_db_file().unwrap();
-            player_info.load_dataframes().unwrap();
+                    Arc::clone(&transport),
+                    warehouse.clone(),
+            )?;
+            let player_info_json = player_info.send_request()?;
+            player_info.save_json_to_db(&player_info_json)?;
+            player_info.dataframes_from_json(&player_info_json)?;
+        }
+        Commands::Savegames { game_ids } => {
+            let mut game_endpoints: Vec<Box<dyn SaveToDB>> = Vec::new();
+            for game_id in game_ids {
+                game_endpoints.push(Box::new(BoxScoreDefensive::new(
+                    nba::params::GameID::ID(game_id.clone()),
+                    Arc::clone(&transport),
+                    warehouse.clone(),
+                )?));
+                game_endpoints.push(Box::new(PlayByPlayV2::new(
+                    Default::default(),
+                    Default::default(),
+                    nba::params::GameID::ID(game_id),
+                    Default::default(),
+                    Arc::clone(&transport),
+                    warehouse.clone(),
+                )?));
+            }
+            let runtime = tokio::runtime::Runtime::new()?;
+            runtime.block_on(futures::future::try_join_all(
+                game_endpoints.iter().map(|endpoint| endpoint.save_to_db_file_async())
+            ))?;
+        }
+        Commands::Sync { target: SyncTarget::Season { season, season_type } } => {
+            // --refresh syncs every game again, not only the ones not done yet
+            let report = sync_season(&transport, &warehouse, Season::S(season), season_type, args.refresh)?;
+            output.write(&[report.games])?;
+            if let Some(e) = report.first_error {
+                return Err(e);
+            }
+        }
+        Commands::Export { table, filter, season, game_ids, player_ids, teams, export_format, dir } => {
+            let query = ExportQuery {
+                table,
+                filter: match filter {
+                    Some(f) => f.parse()?,
+                    None => EventFilter::default(),
+                },
+                season,
+                game_ids,
+                player_ids,
+                teams,
+            };
+            output.write(&[export_table(&warehouse, &query, export_format, Path::new(&dir))?])?;
         }
-        Commands::Scoreboard => {
-            nba::live_data::fetch_scoreboard().unwrap();
+        Commands::Scoreboard { watch: true, interval, .. } => {
+            watch_scoreboard(transport.as_ref(), interval, &output.appending())?;
+        }
+        Commands::Scoreboard { date, yesterday, tomorrow, .. } => {
+            let today = chrono::Local::today().naive_local();
+            let game_date = match (date, yesterday, tomorrow) {
+                (Some(date), _, _) => Some(date),
+                (_, true, _) => today.pred_opt(),
+                (_, _, true) => today.succ_opt(),
+                _ => None,
+            };
+            let scoreboard = match game_date {
+                Some(game_date) => ScoreboardV2::new(GameDate(game_date), Default::default(), Arc::clone(&transport))?.scoreboard()?,
+                None => nba::live_data::fetch_scoreboard(transport.as_ref())?,
+            };
+            output.write(&[scoreboard])?;
+        }
+        Commands::Siren { mut rules, rules_file, sinks, interval } => {
+            if let Some(path) = rules_file {
+                rules.extend(read_rules(&path)?);
+            }
+            let (format, path) = (args.format.unwrap_or(OutputFormat::Table), args.output.as_deref());
+            let sinks = sinks.iter()
+                .map(|spec| parse_sink(spec, format, path))
+                .collect::<NbaResult<Vec<_>>>()?;
+            AlertEngine::new(rules, sinks, warehouse.clone())?.watch(transport.as_ref(), interval)?;
         }
         Commands::Test => {
 
@@ -123,57 +451,123 @@ fn main() {
                 Default::default(),
                 Default::default(),
                 nba::params::GameID::ID("0042100401".to_string()),
-                Some(1628369),
-                Some("reb".to_string()),
-            );
-            p.save_to_db_file().unwrap();
-            let b = p.check_table_exists("fake").unwrap();
+                "player=1628369 and event=rebound".parse()?,
+                Arc::clone(&transport),
+                warehouse.clone(),
+            )?;
+            p.save_to_db_file()?;
+            let b = p.check_table_exists("fake")?;
             println!("{b}");
-            let res = p.search_table("playbyplay_0042100401", vec!["game_id", "player1_name"], vec!["homedescription", "neutraldescription", "visitordescription"], "foul").unwrap();
+            let res = p.search_table("playbyplay", &["game_id", "player1_name"], &["homedescription", "neutraldescription", "visitordescription"], "foul", |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?))
+            })?;
             println!("{:?}", res);
 
         }
-        Commands::Playbyplay {game_id, player_id, keyword, save_videos} => {
-            let pid = player_id.map(|id_str| {
-                id_str.parse::<i64>().unwrap()
-            });
+        Commands::Playbyplay {game_id, player_id, kind, filter, save_videos, events, video_workers, live, follow, interval} => {
+            // -p and -k are shorthands for player= and event= conditions
+            let mut event_filter = match filter {
+                Some(f) => f.parse::<EventFilter>()?,
+                None => EventFilter::default(),
+            };
+            if let Some(id_str) = player_id {
+                event_filter = event_filter.and(format!("player={}", id_str).parse()?);
+            }
+            if let Some(k) = kind {
+                event_filter = event_filter.and(format!("event={}", k).parse()?);
+            }
+            if live {
+                // Events are nested, they read best one json object per line
+                let events_output = Output::new(args.format.unwrap_or(OutputFormat::Ndjson), args.output.as_deref()).appending();
+                let output = output.appending();
+                let write_events = |live_events: &[&PlayByPlayEvent]| {
+                    if events {
+                        let event_records = live_events.iter()
+                            .map(serde_json::to_value)
+                            .collect::<Result<Vec<_>, _>>()?;
+                        events_output.write(&[OutputTable::from_records("Events", event_records)])
+                    } else {
+                        output.write(&[live_events_table(live_events)])
+                    }
+                };
+                if follow {
+                    follow_playbyplay(transport.as_ref(), &game_id, &event_filter, interval, write_events)?;
+                } else {
+                    let live_events = live_events_from_json(&transport.get_json(&playbyplay_url(transport.as_ref(), &game_id))?)?;
+                    write_events(&event_filter.apply(&live_events))?;
+                }
+                return Ok(());
+            }
+            let (start_period, end_period) = event_filter.period_bounds();
             let p = PlayByPlayV2::new(
-                Default::default(),
-                Default::default(),
+                // Only ask for the periods the filter can match, EndPeriod 0
+                // means every period only when StartPeriod is 0 too
+                StartPeriod(Period::P(start_period.unwrap_or(0).clamp(0, 14) as i8)),
+                EndPeriod(Period::P(end_period.unwrap_or(if start_period.is_some() { 14 } else { 0 }).clamp(0, 14) as i8)),
                 nba::params::GameID::ID(game_id),
-                pid,
-                keyword,
-
-            );
+                event_filter,
+                Arc::clone(&transport),
+                warehouse.clone(),
+            )?;
             if save_videos {
-                p.save_video_db().unwrap();
+                p.save_video_db(video_workers)?;
+            }else if events {
+                let event_records = p.events()?.iter()
+                    .map(serde_json::to_value)
+                    .collect::<Result<Vec<_>, _>>()?;
+                // Events are nested, they read best one json object per line
+                let events_output = Output::new(args.format.unwrap_or(OutputFormat::Ndjson), args.output.as_deref());
+                events_output.write(&[OutputTable::from_records("Events", event_records)])?;
             }else{
-                p.print_play_by_play().unwrap();
+                output.write(&[p.play_by_play_table()?])?;
             }
         }
         Commands::Vidforplay {game_id, game_event_id } => {
             let p = VidForPlay::new(
                 nba::params::GameID::ID(game_id),
-                game_event_id,
-            );
-            let u = p.get_video_url().unwrap();
-            webbrowser::open(&u).unwrap();
+                nba::params::GameEventID::ID(game_event_id),
+                Arc::clone(&transport),
+                warehouse.clone(),
+            )?;
+            let u = p.get_video_url()?;
+            webbrowser::open(&u)?;
             println!("{}", u);
         }
-        Commands::Boxscore {game_id, defensive} => {
+        Commands::Boxscore { game_ids, watch: true, interval, .. } => {
+            watch_boxscores(transport.as_ref(), &game_ids, interval, &output.appending())?;
+        }
+        Commands::Boxscore {game_ids, defensive, ..} => {
             if defensive {
-                let boxscore = BoxScoreDefensive ::new(
-                    nba::params::GameID::ID(game_id)
-                );
-                boxscore.save_to_db_file().unwrap();
-                let boxscore_frames = boxscore.load_dataframes().unwrap();
-                for (data_set_name, dataframe) in boxscore_frames {
-                    println!("{}\n{}", data_set_name, dataframe);
+                let mut boxscores = Vec::new();
+                for game_id in game_ids {
+                    boxscores.push(BoxScoreDefensive ::new(
+                        nba::params::GameID::ID(game_id),
+                        Arc::clone(&transport),
+                        warehouse.clone(),
+                    )?);
                 }
+                let runtime = tokio::runtime::Runtime::new()?;
+                let all_boxscore_json = runtime.block_on(futures::future::try_join_all(
+                    boxscores.iter().map(|boxscore| boxscore.send_request_async())
+                ))?;
+                // Every game's result sets end up in one table per result set
+                let mut boxscore_tables = Vec::new();
+                for (boxscore, boxscore_json) in boxscores.iter().zip(all_boxscore_json) {
+                    boxscore.save_json_to_db(&boxscore_json)?;
+                    for table in dataframe_tables(boxscore.dataframes_from_json(&boxscore_json)?) {
+                        merge_table(&mut boxscore_tables, table);
+                    }
+                }
+                output.write(&boxscore_tables)?;
             }else {
-                nba::live_data::fetch_scoreboard().unwrap();
+                let mut boxscore_tables = Vec::new();
+                for game_id in &game_ids {
+                    merge_table(&mut boxscore_tables, nba::live_data::fetch_boxscore(transport.as_ref(), game_id)?);
+                }
+                output.write(&boxscore_tables)?;
             }
             
         }
     }
+    Ok(())
 }
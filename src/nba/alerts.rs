@@ -0,0 +1,568 @@
+use crate::nba::error::{NbaError, NbaResult};
+use crate::nba::events::{GameClock, PersonRole, PlayByPlayEvent};
+use crate::nba::filter::{split_and, split_comparison, Op};
+use crate::nba::live_data::{live_events_from_json, playbyplay_url, scoreboard_url, scoreboard_snapshots, GameSnapshot};
+use crate::nba::output::{Output, OutputFormat, OutputTable};
+use crate::nba::transport::Transport;
+use crate::nba::warehouse::Warehouse;
+use crate::nba::watch::FeedPoller;
+
+use serde::Serialize;
+use std::{collections::HashMap, env, fmt, fs, io::Write, process::{Command, Stdio}, str::FromStr, thread, time::Duration};
+
+#[derive(Debug, Clone)]
+enum RuleCondition {
+    Period(Op, i64),
+    /// Time left in the period
+    Clock(Op, GameClock),
+    /// Absolute score difference
+    Margin(Op, i64),
+    /// Points of the current unanswered run
+    Run(Op, i64),
+    /// Points of a player, one of `Player` if the rule names any
+    Points(Op, i64),
+    Player(Vec<i64>),
+    Team(Vec<String>),
+}
+
+/// A named alert rule, `and` separated conditions that must all hold for a
+/// live game:
+///
+/// `clutch: period>=4 and clock<=3:00 and margin<=5`
+///
+/// `period`, `clock`, `margin`, `run` and `points` take any comparison,
+/// `player` and `team` a comma separated list. `points` is about the players
+/// listed, `run` and `points` about the teams listed if there are any. Both
+/// come from the live play by play, where the order points were scored in
+/// is known.
+#[derive(Debug, Clone)]
+pub struct AlertRule {
+    pub name: String,
+    source: String,
+    conditions: Vec<RuleCondition>,
+}
+
+impl FromStr for AlertRule {
+    type Err = NbaError;
+    fn from_str(s: &str) -> NbaResult<Self> {
+        let (name, source) = s.split_once(':')
+            .map(|(name, source)| (name.trim(), source.trim()))
+            .ok_or_else(|| NbaError::InvalidParam(format!("expected a rule like \"name: conditions\", got {:?}", s)))?;
+        let invalid = |clause: &str, why: &str| NbaError::InvalidParam(format!("bad condition {:?} in rule {}: {}", clause, name, why));
+        let mut conditions = Vec::new();
+        for clause in split_and(source) {
+            let (field, op, value) = split_comparison(clause).ok_or_else(|| invalid(clause, "expected field=value"))?;
+            let number = || value.parse::<i64>().map_err(|_| invalid(clause, "expected a number"));
+            let list = || value.split(',').map(str::trim).filter(|v| !v.is_empty());
+            if matches!(field.as_str(), "player" | "team") && op != Op::Eq {
+                return Err(invalid(clause, "only = works on this field"));
+            }
+            conditions.push(match field.as_str() {
+                "period" => RuleCondition::Period(op, number()?),
+                "clock" => RuleCondition::Clock(op, value.parse().map_err(|_| invalid(clause, "expected a clock like 3:00"))?),
+                "margin" => RuleCondition::Margin(op, number()?.abs()),
+                "run" => RuleCondition::Run(op, number()?),
+                "points" => RuleCondition::Points(op, number()?),
+                "player" => RuleCondition::Player(list()
+                    .map(|id| id.parse().map_err(|_| invalid(clause, "player ids are numbers")))
+                    .collect::<NbaResult<_>>()?),
+                "team" => RuleCondition::Team(list().map(str::to_uppercase).collect()),
+                _ => return Err(invalid(clause, "fields are period, clock, margin, run, points, player and team")),
+            });
+        }
+        if conditions.is_empty() {
+            return Err(NbaError::InvalidParam(format!("rule {} has no conditions", name)));
+        }
+        let has_points = conditions.iter().any(|c| matches!(c, RuleCondition::Points(..)));
+        if !has_points && conditions.iter().any(|c| matches!(c, RuleCondition::Player(_))) {
+            return Err(NbaError::InvalidParam(format!("rule {} names a player without a points condition", name)));
+        }
+        Ok(AlertRule { name: name.to_string(), source: source.to_string(), conditions })
+    }
+}
+
+impl fmt::Display for AlertRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.name, self.source)
+    }
+}
+
+impl AlertRule {
+    /// Whether every condition holds for `game`, `run` being its current run
+    /// and `points` what each of its players scored so far.
+    pub fn matches(&self, game: &GameSnapshot, run: Option<&Run>, points: &[PlayerPoints]) -> bool {
+        let teams = self.conditions.iter().find_map(|c| match c {
+            RuleCondition::Team(teams) => Some(teams),
+            _ => None,
+        });
+        let players = self.conditions.iter().find_map(|c| match c {
+            RuleCondition::Player(ids) => Some(ids),
+            _ => None,
+        });
+        let team_wanted = |tricode: &str| teams.is_none_or(|teams| teams.iter().any(|t| t == tricode));
+        self.conditions.iter().all(|condition| match condition {
+            RuleCondition::Period(op, period) => op.compare(game.period, *period),
+            RuleCondition::Clock(op, clock) => game.clock
+                .is_some_and(|game_clock| op.compare(game_clock.seconds_remaining(), clock.seconds_remaining())),
+            RuleCondition::Margin(op, margin) => op.compare(game.margin().abs(), *margin),
+            RuleCondition::Run(op, points) => run
+                .filter(|run| team_wanted(&run.tricode))
+                .is_some_and(|run| op.compare(run.points, *points)),
+            RuleCondition::Points(op, wanted) => points.iter()
+                .filter(|player| players.is_none_or(|ids| ids.contains(&player.person_id)))
+                .filter(|player| team_wanted(&player.tricode))
+                .any(|player| op.compare(player.points, *wanted)),
+            RuleCondition::Player(_) => true,
+            RuleCondition::Team(teams) => teams.iter().any(|t| *t == game.home.tricode || *t == game.away.tricode),
+        })
+    }
+}
+
+/// Rules of a file, one per line, blank lines and `#` comments skipped.
+pub fn read_rules(path: &str) -> NbaResult<Vec<AlertRule>> {
+    fs::read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::parse)
+        .collect()
+}
+
+/// Points a team scored in a row without the other team scoring.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Run {
+    pub tricode: String,
+    pub points: i64,
+}
+
+/// What a player scored so far in a game.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerPoints {
+    pub person_id: i64,
+    pub name: String,
+    pub tricode: String,
+    pub points: i64,
+}
+
+// The current run and each player's points, pieced together from a game's
+// live play by play in the order the points were scored
+#[derive(Default)]
+struct RunTracker {
+    last_event_num: i64,
+    /// Visitor and home score after the last event taken in
+    score: (i64, i64),
+    run: Option<Run>,
+    points: Vec<PlayerPoints>,
+}
+
+impl RunTracker {
+    /// Takes in the events after the last one seen.
+    fn update(&mut self, events: &[PlayByPlayEvent]) {
+        for event in events {
+            if event.event_num <= self.last_event_num {
+                continue;
+            }
+            self.last_event_num = event.event_num;
+            let score = match event.score {
+                Some(score) => (score.visitor as i64, score.home as i64),
+                None => continue,
+            };
+            let scored = (score.0 - self.score.0, score.1 - self.score.1);
+            self.score = score;
+            let (points, scorer) = match (scored, &event.players[0]) {
+                ((0, 0), _) => continue,
+                ((points, 0), Some(scorer)) | ((0, points), Some(scorer)) if points > 0 => (points, scorer),
+                // A corrected score, who scored is unknown
+                _ => {
+                    self.run = None;
+                    continue;
+                }
+            };
+            let tricode = scorer.team_abbreviation.clone().unwrap_or_default();
+            match &mut self.run {
+                Some(run) if run.tricode == tricode => run.points += points,
+                run => *run = Some(Run { tricode: tricode.clone(), points }),
+            }
+            if !matches!(scorer.role, PersonRole::HomePlayer | PersonRole::VisitorPlayer) {
+                continue;
+            }
+            match self.points.iter_mut().find(|player| player.person_id == scorer.id) {
+                Some(player) => player.points += points,
+                None => self.points.push(PlayerPoints {
+                    person_id: scorer.id,
+                    name: scorer.name.clone().unwrap_or_default(),
+                    tricode,
+                    points,
+                }),
+            }
+        }
+    }
+}
+
+/// A rule that fired for a game, what every sink is handed.
+#[derive(Debug, Clone, Serialize)]
+pub struct Alert {
+    pub rule: String,
+    pub condition: String,
+    pub game_id: String,
+    pub message: String,
+    pub score: String,
+    pub status: String,
+    pub fired_at: String,
+}
+
+/// Somewhere alerts are delivered to.
+pub trait AlertSink {
+    fn deliver(&self, alert: &Alert) -> NbaResult<()>;
+}
+
+/// Writes each alert as a one row table, in the `--format` asked for.
+pub struct StdoutSink {
+    output: Output,
+}
+
+impl AlertSink for StdoutSink {
+    fn deliver(&self, alert: &Alert) -> NbaResult<()> {
+        self.output.write(&[OutputTable::from_records("Alerts", vec![serde_json::to_value(alert)?])])
+    }
+}
+
+/// Runs a shell command per alert, with the alert as json on stdin and in
+/// `NBA_ALERT_RULE`, `NBA_ALERT_GAME_ID` and `NBA_ALERT_MESSAGE`.
+pub struct CommandSink {
+    command: String,
+}
+
+impl AlertSink for CommandSink {
+    fn deliver(&self, alert: &Alert) -> NbaResult<()> {
+        let (shell, flag) = if env::consts::OS == "windows" { ("cmd", "/C") } else { ("sh", "-c") };
+        let mut child = Command::new(shell)
+            .args([flag, &self.command])
+            .env("NBA_ALERT_RULE", &alert.rule)
+            .env("NBA_ALERT_GAME_ID", &alert.game_id)
+            .env("NBA_ALERT_MESSAGE", &alert.message)
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|e| NbaError::Alert(format!("could not run {}: {}", self.command, e)))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // A command that doesn't read its input is fine
+            let _ = stdin.write_all(serde_json::to_string(alert)?.as_bytes());
+        }
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(NbaError::Alert(format!("{} exited with {}", self.command, status)));
+        }
+        Ok(())
+    }
+}
+
+/// POSTs each alert as a json object.
+pub struct WebhookSink {
+    url: String,
+    agent: ureq::Agent,
+}
+
+impl AlertSink for WebhookSink {
+    fn deliver(&self, alert: &Alert) -> NbaResult<()> {
+        self.agent.post(&self.url)
+            .send_json(serde_json::to_value(alert)?)
+            .map_err(|e| NbaError::Alert(e.to_string()))?;
+        Ok(())
+    }
+}
+
+/// A sink from its command line spec: `stdout`, `exec:<shell command>` or
+/// `webhook:<url>`. `stdout` writes in `format` to `path` if one is given.
+pub fn parse_sink(spec: &str, format: OutputFormat, path: Option<&str>) -> NbaResult<Box<dyn AlertSink>> {
+    if spec.eq_ignore_ascii_case("stdout") {
+        return Ok(Box::new(StdoutSink { output: Output::new(format, path).appending() }));
+    }
+    match spec.split_once(':') {
+        Some(("exec", command)) if !command.trim().is_empty() => Ok(Box::new(CommandSink { command: command.trim().to_string() })),
+        Some(("webhook", url)) if !url.trim().is_empty() => Ok(Box::new(WebhookSink {
+            url: url.trim().to_string(),
+            agent: ureq::AgentBuilder::new().timeout(Duration::from_secs(10)).build(),
+        })),
+        _ => Err(NbaError::InvalidParam(format!("unknown sink {}, expected stdout, exec:<command> or webhook:<url>", spec))),
+    }
+}
+
+/// Checks rules against each poll of the live scoreboard and play by play
+/// and delivers what fires. Fired alerts are recorded in the warehouse so a
+/// rule fires once per game, even across restarts.
+pub struct AlertEngine {
+    rules: Vec<AlertRule>,
+    sinks: Vec<Box<dyn AlertSink>>,
+    warehouse: Warehouse,
+    feeds: HashMap<String, FeedPoller>,
+    runs: HashMap<String, RunTracker>,
+}
+
+impl AlertEngine {
+    pub fn new(rules: Vec<AlertRule>, sinks: Vec<Box<dyn AlertSink>>, warehouse: Warehouse) -> NbaResult<AlertEngine> {
+        if rules.is_empty() {
+            return Err(NbaError::InvalidParam("no alert rules given".to_string()));
+        }
+        Ok(AlertEngine { rules, sinks, warehouse, feeds: HashMap::new(), runs: HashMap::new() })
+    }
+
+    /// Checks every rule against the live games of a poll and delivers the
+    /// alerts that fire. A sink failing doesn't stop the others.
+    pub fn evaluate(&mut self, games: &[GameSnapshot]) -> NbaResult<Vec<Alert>> {
+        let mut fired = Vec::new();
+        let no_runs = RunTracker::default();
+        for game in games.iter().filter(|game| game.status == 2) {
+            let runs = self.runs.get(&game.game_id).unwrap_or(&no_runs);
+            for rule in &self.rules {
+                if !rule.matches(game, runs.run.as_ref(), &runs.points) || self.warehouse.has_fired(&rule.name, &game.game_id)? {
+                    continue;
+                }
+                let alert = Alert {
+                    rule: rule.name.clone(),
+                    condition: rule.source.clone(),
+                    game_id: game.game_id.clone(),
+                    message: format!("{}: {} ({})", rule.name, game.score_line(), game.status_text),
+                    score: game.score_line(),
+                    status: game.status_text.clone(),
+                    fired_at: chrono::Local::now().to_rfc3339(),
+                };
+                for sink in &self.sinks {
+                    if let Err(e) = sink.deliver(&alert) {
+                        eprintln!("{}", e);
+                    }
+                }
+                self.warehouse.record_alert(&alert.rule, &alert.game_id, &alert.message)?;
+                fired.push(alert);
+            }
+        }
+        Ok(fired)
+    }
+
+    /// Evaluates the rules whenever the live scoreboard or the play by play
+    /// of a live game changed, polling them every `interval`, until every
+    /// game is final or the scoreboard turns out to have none.
+    pub fn watch(&mut self, transport: &dyn Transport, interval: Duration) -> NbaResult<()> {
+        let mut scoreboard = FeedPoller::new(scoreboard_url(transport));
+        let mut games = Vec::new();
+        loop {
+            let mut changed = false;
+            if let Some(json) = scoreboard.poll(transport)? {
+                games = scoreboard_snapshots(&json)?;
+                if games.is_empty() {
+                    eprintln!("no games on the scoreboard");
+                    return Ok(());
+                }
+                changed = true;
+            }
+            for game in games.iter().filter(|game| game.status == 2) {
+                changed |= self.poll_playbyplay(transport, &game.game_id)?;
+            }
+            if changed {
+                self.evaluate(&games)?;
+            }
+            if games.iter().all(|game| game.status == 3) {
+                return Ok(());
+            }
+            thread::sleep(interval);
+        }
+    }
+
+    // Takes in what's new in a game's live play by play, true if anything was
+    fn poll_playbyplay(&mut self, transport: &dyn Transport, game_id: &str) -> NbaResult<bool> {
+        let feed = self.feeds.entry(game_id.to_string())
+            .or_insert_with(|| FeedPoller::new(playbyplay_url(transport, game_id)));
+        match feed.poll(transport) {
+            Ok(Some(json)) => {
+                let events = live_events_from_json(&json)?;
+                self.runs.entry(game_id.to_string()).or_default().update(&events);
+                Ok(true)
+            }
+            // The feed is only published once the game starts
+            Ok(None) | Err(NbaError::HttpStatus { status: 403 | 404, .. }) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nba::event_codes::{EventAction, EventMsgType};
+    use crate::nba::events::{EventPlayer, Score};
+    use crate::nba::live_data::TeamScore;
+    use serde_json::{json, Value};
+
+    // Serves a scoreboard without games, fails anything else
+    struct EmptyScoreboard;
+
+    impl Transport for EmptyScoreboard {
+        fn stats_base_url(&self) -> &str {
+            "https://stats.nba.com/stats"
+        }
+        fn cdn_base_url(&self) -> &str {
+            "https://cdn.nba.com"
+        }
+        fn get_json(&self, url: &str) -> NbaResult<Value> {
+            if url == scoreboard_url(self) {
+                Ok(json!({"scoreboard": {"games": []}}))
+            } else {
+                Err(NbaError::InvalidParam(format!("unexpected request for {}", url)))
+            }
+        }
+        fn get_bytes(&self, url: &str) -> NbaResult<Vec<u8>> {
+            Err(NbaError::InvalidParam(format!("unexpected request for {}", url)))
+        }
+    }
+
+    fn game(period: i64, clock: &str, away: i64, home: i64) -> GameSnapshot {
+        GameSnapshot {
+            game_id: "0042100401".to_string(),
+            status: 2,
+            status_text: format!("Q{} {}", period, clock),
+            period,
+            clock: Some(clock.parse().unwrap()),
+            home: TeamScore { tricode: "GSW".to_string(), score: home },
+            away: TeamScore { tricode: "BOS".to_string(), score: away },
+        }
+    }
+
+    // A made shot of `player_id`, home players are on GSW, the score after
+    // it is `away - home`
+    fn basket(event_num: i64, player_id: i64, home_player: bool, away: u32, home: u32) -> PlayByPlayEvent {
+        PlayByPlayEvent {
+            game_id: "0042100401".to_string(),
+            event_num,
+            event_type: EventMsgType::MadeShot,
+            action: EventAction::Other(0),
+            period: 4,
+            clock: "5:00".parse().unwrap(),
+            home_description: None,
+            neutral_description: None,
+            visitor_description: None,
+            score: Some(Score { visitor: away, home }),
+            margin: Some(home as i64 - away as i64),
+            shot_value: Some(2),
+            players: [
+                Some(EventPlayer {
+                    id: player_id,
+                    name: Some(format!("Player {}", player_id)),
+                    team_id: None,
+                    team_abbreviation: Some(if home_player { "GSW" } else { "BOS" }.to_string()),
+                    role: if home_player { PersonRole::HomePlayer } else { PersonRole::VisitorPlayer },
+                }),
+                None,
+                None,
+            ],
+            video_available: false,
+        }
+    }
+
+    fn points(person_id: i64, tricode: &str, points: i64) -> PlayerPoints {
+        PlayerPoints { person_id, name: String::new(), tricode: tricode.to_string(), points }
+    }
+
+    #[test]
+    fn parses_name_and_conditions() {
+        let rule: AlertRule = "clutch: period>=4 and clock<=3:00 and margin<=5".parse().unwrap();
+        assert_eq!(rule.name, "clutch");
+        assert_eq!(rule.conditions.len(), 3);
+        assert_eq!(rule.to_string(), "clutch: period>=4 and clock<=3:00 and margin<=5");
+    }
+
+    #[test]
+    fn rejects_bad_rules() {
+        for rule in [
+            "period>=4",
+            "empty: ",
+            "typo: quarter>=4",
+            "clock: clock<=3 minutes",
+            "who: player=201939",
+            "teams: team>=BOS and points>=30",
+            "ids: player=curry and points>=30",
+        ] {
+            assert!(rule.parse::<AlertRule>().is_err(), "{} parsed", rule);
+        }
+    }
+
+    #[test]
+    fn matches_game_state() {
+        let rule: AlertRule = "clutch: period>=4 and clock<=3:00 and margin<=5".parse().unwrap();
+        assert!(rule.matches(&game(4, "2:59", 100, 104), None, &[]));
+        assert!(!rule.matches(&game(4, "3:01", 100, 104), None, &[]));
+        assert!(!rule.matches(&game(3, "2:59", 100, 104), None, &[]));
+        assert!(!rule.matches(&game(4, "2:59", 100, 106), None, &[]));
+    }
+
+    #[test]
+    fn matches_runs_of_the_teams_named() {
+        let rule: AlertRule = "run: run>=10 and team=bos".parse().unwrap();
+        let boston_run = Run { tricode: "BOS".to_string(), points: 10 };
+        let warriors_run = Run { tricode: "GSW".to_string(), points: 12 };
+        assert!(rule.matches(&game(2, "6:00", 50, 40), Some(&boston_run), &[]));
+        assert!(!rule.matches(&game(2, "6:00", 50, 40), Some(&warriors_run), &[]));
+        assert!(!rule.matches(&game(2, "6:00", 50, 40), None, &[]));
+    }
+
+    #[test]
+    fn matches_points_of_the_players_named() {
+        let rule: AlertRule = "curry: points>=30 and player=201939".parse().unwrap();
+        let scored = [points(1628369, "BOS", 34), points(201939, "GSW", 29)];
+        assert!(!rule.matches(&game(4, "8:00", 90, 90), None, &scored));
+        let scored = [points(1628369, "BOS", 34), points(201939, "GSW", 31)];
+        assert!(rule.matches(&game(4, "8:00", 90, 90), None, &scored));
+
+        let anyone: AlertRule = "big night: points>=30 and team=GSW".parse().unwrap();
+        assert!(!anyone.matches(&game(4, "8:00", 90, 90), None, &[points(1628369, "BOS", 34)]));
+    }
+
+    #[test]
+    fn runs_follow_scoring_order() {
+        let mut tracker = RunTracker::default();
+        tracker.update(&[
+            basket(1, 201939, true, 0, 3),
+            basket(2, 1628369, false, 2, 3),
+            basket(3, 1628369, false, 4, 3),
+            basket(4, 1627759, false, 7, 3),
+        ]);
+        assert_eq!(tracker.run, Some(Run { tricode: "BOS".to_string(), points: 7 }));
+        // Both teams scored between polls, the events still tell who scored last
+        tracker.update(&[basket(5, 1628369, false, 9, 3), basket(6, 201939, true, 9, 5)]);
+        assert_eq!(tracker.run, Some(Run { tricode: "GSW".to_string(), points: 2 }));
+    }
+
+    #[test]
+    fn events_already_seen_are_skipped() {
+        let mut tracker = RunTracker::default();
+        let events = [basket(1, 201939, true, 0, 3), basket(2, 201939, true, 0, 5)];
+        tracker.update(&events);
+        tracker.update(&events);
+        assert_eq!(tracker.run, Some(Run { tricode: "GSW".to_string(), points: 5 }));
+        assert_eq!(tracker.points, vec![PlayerPoints { person_id: 201939, name: "Player 201939".to_string(), tricode: "GSW".to_string(), points: 5 }]);
+    }
+
+    #[test]
+    fn player_points_add_up() {
+        let mut tracker = RunTracker::default();
+        tracker.update(&[
+            basket(1, 201939, true, 0, 3),
+            basket(2, 1628369, false, 2, 3),
+            basket(3, 201939, true, 2, 4),
+        ]);
+        let scored: Vec<(i64, i64)> = tracker.points.iter().map(|player| (player.person_id, player.points)).collect();
+        assert_eq!(scored, vec![(201939, 4), (1628369, 2)]);
+    }
+
+    #[test]
+    fn corrected_score_ends_the_run() {
+        let mut tracker = RunTracker::default();
+        tracker.update(&[basket(1, 201939, true, 0, 3), basket(2, 201939, true, 0, 2)]);
+        assert_eq!(tracker.run, None);
+    }
+
+    #[test]
+    fn watching_a_day_without_games_returns() {
+        let rule: AlertRule = "clutch: period>=4 and margin<=5".parse().unwrap();
+        let mut engine = AlertEngine::new(vec![rule], Vec::new(), Warehouse::open(":memory:", None).unwrap()).unwrap();
+        engine.watch(&EmptyScoreboard, Duration::from_millis(1)).unwrap();
+    }
+}
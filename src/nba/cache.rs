@@ -0,0 +1,189 @@
+use crate::nba::transport::{Conditional, Transport, Validators};
+use crate::nba::error::{NbaError, NbaResult};
+
+use std::{fs, path::PathBuf, sync::Arc, time::{Duration, SystemTime, UNIX_EPOCH}};
+use chrono::Datelike;
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+
+pub const DEFAULT_CACHE_DIR: &str = ".nba_siren_cache";
+
+/// How long a cached response stays fresh.
+#[derive(Debug, PartialEq)]
+pub enum CacheTtl {
+    Seconds(u64),
+    Forever,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    url: String,
+    fetched_at: u64,
+    ttl_seconds: Option<u64>,
+    body: Value,
+}
+
+/// Wraps another transport and keeps every json response on disk, keyed by
+/// the full request url. In offline mode the wrapped transport is never used,
+/// with `refresh` it always is and the cache is only written.
+pub struct CachedTransport {
+    inner: Arc<dyn Transport>,
+    cache_dir: PathBuf,
+    offline: bool,
+    refresh: bool,
+}
+
+impl CachedTransport {
+    pub fn new(inner: Arc<dyn Transport>, cache_dir: &str, offline: bool, refresh: bool) -> NbaResult<CachedTransport> {
+        fs::create_dir_all(cache_dir)?;
+        Ok(CachedTransport {
+            inner,
+            cache_dir: PathBuf::from(cache_dir),
+            offline,
+            refresh,
+        })
+    }
+
+    fn entry_path(&self, url: &str) -> PathBuf {
+        let endpoint = endpoint_name(url);
+        let file_name: String = endpoint.chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        self.cache_dir.join(format!("{}_{:016x}.json", file_name, fnv1a_hash(url)))
+    }
+
+    fn read_entry(&self, url: &str) -> Option<CacheEntry> {
+        let data = fs::read_to_string(self.entry_path(url)).ok()?;
+        let entry: CacheEntry = serde_json::from_str(&data).ok()?;
+        // Guard against the astronomically unlikely hash collision
+        if entry.url != url {
+            return None;
+        }
+        Some(entry)
+    }
+
+    fn write_entry(&self, url: &str, body: &Value, ttl: CacheTtl) -> NbaResult<()> {
+        let ttl_seconds = match ttl {
+            CacheTtl::Seconds(s) => Some(s),
+            CacheTtl::Forever => None,
+        };
+        let entry = CacheEntry {
+            url: url.to_string(),
+            fetched_at: now_seconds(),
+            ttl_seconds,
+            body: body.clone(),
+        };
+        fs::write(self.entry_path(url), serde_json::to_string(&entry)?)?;
+        Ok(())
+    }
+}
+
+impl Transport for CachedTransport {
+    fn stats_base_url(&self) -> &str {
+        self.inner.stats_base_url()
+    }
+    fn cdn_base_url(&self) -> &str {
+        self.inner.cdn_base_url()
+    }
+    fn get_json(&self, url: &str) -> NbaResult<Value> {
+        let cached = self.read_entry(url);
+        if self.offline {
+            return cached.map(|entry| entry.body)
+                .ok_or_else(|| NbaError::NotCached(url.to_string()));
+        }
+        if let Some(entry) = cached.filter(|_| !self.refresh) {
+            let age = now_seconds().saturating_sub(entry.fetched_at);
+            if entry.ttl_seconds.is_none_or(|ttl| age < ttl) {
+                return Ok(entry.body);
+            }
+        }
+        let body = self.inner.get_json(url)?;
+        self.write_entry(url, &body, ttl_for_response(url, &body))?;
+        Ok(body)
+    }
+    // Polling asks the server directly, a cached copy is what offline mode gets
+    fn get_json_if_modified(&self, url: &str, validators: &Validators) -> NbaResult<Conditional> {
+        if self.offline {
+            return self.get_json(url).map(|body| Conditional::Modified(body, Validators::default()));
+        }
+        let response = self.inner.get_json_if_modified(url, validators)?;
+        if let Conditional::Modified(body, _) = &response {
+            self.write_entry(url, body, ttl_for_response(url, body))?;
+        }
+        Ok(response)
+    }
+    fn get_bytes(&self, url: &str) -> NbaResult<Vec<u8>> {
+        if self.offline {
+            return Err(NbaError::NotCached(url.to_string()));
+        }
+        self.inner.get_bytes(url)
+    }
+}
+
+/// Picks how long a response can be reused. Finished games never change so
+/// their box scores and play by play are kept forever, anything that tracks
+/// a live game expires within seconds.
+pub fn ttl_for_response(url: &str, body: &Value) -> CacheTtl {
+    let endpoint = endpoint_name(url).to_lowercase();
+    if endpoint.starts_with("todaysscoreboard") || endpoint == "scoreboardv2" {
+        CacheTtl::Seconds(10)
+    } else if endpoint.starts_with("boxscore") || endpoint.starts_with("playbyplay") {
+        if game_is_final(url, body) { CacheTtl::Forever } else { CacheTtl::Seconds(10) }
+    } else if endpoint == "videoeventsasset" {
+        CacheTtl::Forever
+    } else if endpoint == "commonallplayers" || endpoint == "commonplayerinfo" || endpoint == "teamdetails" {
+        CacheTtl::Seconds(24 * 60 * 60)
+    } else {
+        CacheTtl::Seconds(60 * 60)
+    }
+}
+
+fn game_is_final(url: &str, body: &Value) -> bool {
+    // Live cdn feeds carry their own status, 3 is final
+    if let Some(status) = body["game"]["gameStatus"].as_i64() {
+        return status == 3;
+    }
+    // boxscoresummaryv2 reports the status in its GameSummary result set
+    if let Some(result_sets) = body["resultSets"].as_array() {
+        for data_set in result_sets.iter().filter(|d| d["name"] == "GameSummary") {
+            let status_pos = data_set["headers"].as_array()
+                .and_then(|headers| headers.iter().position(|h| h == "GAME_STATUS_ID"));
+            if let (Some(pos), Some(row)) = (status_pos, data_set["rowSet"][0].as_array()) {
+                return row[pos].as_i64() == Some(3);
+            }
+        }
+    }
+    // Otherwise any game from a past season is over
+    let game_id = url.split(['?', '&'])
+        .find_map(|param| param.strip_prefix("GameID="))
+        .unwrap_or("");
+    match game_id.get(3..5).and_then(|yy| yy.parse::<i32>().ok()) {
+        Some(yy) => 2000 + yy < current_season_start_year(),
+        None => false,
+    }
+}
+
+fn current_season_start_year() -> i32 {
+    let today = chrono::Utc::now();
+    // Seasons tip off in October
+    if today.month() >= 10 { today.year() } else { today.year() - 1 }
+}
+
+fn endpoint_name(url: &str) -> &str {
+    let path = url.split('?').next().unwrap_or(url);
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+fn now_seconds() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or(Duration::ZERO).as_secs()
+}
+
+// FNV-1a, stable across builds unlike std's DefaultHasher
+fn fnv1a_hash(s: &str) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in s.bytes() {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
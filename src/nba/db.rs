@@ -1,239 +1,392 @@
 
 use crate::nba::endpoints::NBAEndpoint;
+use crate::nba::error::{missing, NbaError, NbaResult};
+use crate::nba::warehouse::{check_column_types, existing_column_types, infer_column_types, registered_table, table_columns, SqlType, TableSchema, Warehouse, REQUEST_URL_COLUMN};
 
 use polars::prelude::*;
+use serde::de::DeserializeOwned;
 use serde_json::Value;
 use std::{collections::HashMap, time::Instant};
-use anyhow::Result;
 
-use rusqlite::{Connection, params};
+use rusqlite::{params, params_from_iter, types::Value as SqlValue, Connection, Row};
 
 pub trait SaveToDB: NBAEndpoint {
-    fn get_db_connection(&self) -> &Connection;
-    fn check_table_exists(&self, table_name: &str) -> Result<bool> {
+    fn get_warehouse(&self) -> &Warehouse;
+    fn get_db_connection(&self) -> &Connection {
+        self.get_warehouse().connection()
+    }
+    fn check_table_exists(&self, table_name: &str) -> NbaResult<bool> {
         let db_conn = self.get_db_connection();
         let mut find_table_stmt = db_conn.prepare("SELECT name FROM sqlite_master WHERE type='table' AND name=? ")?;
-        let found_table = find_table_stmt.query_row::<String,_,  _>(params![table_name], |r| {
-            let t = r.get(0);
-            t
-        });
+        let found_table = find_table_stmt.query_row::<String,_,  _>(params![table_name], |r| r.get(0));
         Ok(found_table.is_ok())
     }
-    fn search_table(&self, table_name: &str, select_column_names: Vec<&str>, search_column_names: Vec<&str>, keyword: &str ) -> Result<Vec<HashMap<String, String>>> {
+    /// Rows of `table_name` where any of `search_column_names` contains
+    /// `keyword`, each mapped to a `T` by `map_row` from the selected columns
+    /// in order.
+    fn search_table<T, F>(&self, table_name: &str, select_column_names: &[&str], search_column_names: &[&str], keyword: &str, map_row: F) -> NbaResult<Vec<T>>
+    where
+        Self: Sized,
+        F: FnMut(&Row<'_>) -> rusqlite::Result<T>,
+    {
         let db_conn = self.get_db_connection();
-        let where_stmt = search_column_names.iter().map(|&col|{
-            format!("{col} like '%{keyword}%'")
-        }).collect::<Vec<String>>().join(" OR ");
-        let select_stmt = select_column_names.join(", ");
-        let search_stmt = format!("SELECT {} FROM {table_name} WHERE {}", &select_stmt, &where_stmt);
+        let where_stmt = search_column_names.iter()
+            .map(|col| format!("{} LIKE ?1 ESCAPE '\\'", quote_identifier(col)))
+            .collect::<Vec<String>>()
+            .join(" OR ");
+        let select_stmt = select_column_names.iter()
+            .map(|col| quote_identifier(col))
+            .collect::<Vec<String>>()
+            .join(", ");
+        let search_stmt = format!("SELECT {} FROM {} WHERE {}", select_stmt, quote_identifier(table_name), where_stmt);
+        let like_pattern = format!("%{}%", keyword.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"));
         let mut sql_search_stmt = db_conn.prepare(&search_stmt)?;
-        let mut results: Vec<HashMap<String, String>> = Vec::new();
-        println!("{search_stmt}");
-        let results_iter = sql_search_stmt.query_map::<_, _, _>(params![], |row|{
-            let mut r:HashMap<String, String> = HashMap::new();
-            select_column_names.iter().enumerate().for_each(|(i, &col)|{
-                let row_val;
-                if col.contains("_id") {
-                    let num: i32 = row.get(i).unwrap_or(0);
-                    row_val = num.to_string();
-                }else {
-                    row_val = row.get(i).unwrap_or(Default::default());
-                }
-                r.insert(col.to_string(), row_val);
-            });
-            results.push(r);
-            Ok(())
-        })?;
-        results_iter.for_each(drop);
-
+        let results = sql_search_stmt.query_map(params![like_pattern], map_row)?
+            .collect::<rusqlite::Result<Vec<T>>>()?;
         Ok(results)
-
     }
-    fn get_insert_row_statement(&self, table_name: &str, row: &Vec<Value>) -> String {
-        let mut insert_stmt = format!("INSERT INTO {} VALUES (NULL,", table_name);
-        let row_size = row.len();
-        for (pos, col_val) in row.iter().enumerate() {
-            if col_val.is_null() {
-                insert_stmt.push_str("NULL");
-            }else if col_val.is_f64() {
-                let num_val = col_val.as_f64().unwrap().to_string();
-                insert_stmt.push_str(&num_val);
-            }else if col_val.is_i64() {
-                let num_val = col_val.as_i64().unwrap().to_string();
-                insert_stmt.push_str(&num_val);
-            }else if col_val.is_string() {
-                let col_val_str = col_val.as_str().unwrap_or("").replace("'", "");
-                insert_stmt.push('\'');
-                insert_stmt.push_str(&col_val_str);
-                insert_stmt.push('\'');
-            }
-            if pos != row_size -1 {
-                insert_stmt.push(',');
-            } 
-            
+    /// `INSERT` for one row of `column_names`, bound in order. When `key` is
+    /// given a row whose key is already stored overwrites it instead.
+    fn get_upsert_row_statement(&self, table_name: &str, column_names: &[String], key: &[&str]) -> String {
+        let columns: Vec<String> = column_names.iter().map(|col| quote_identifier(col)).collect();
+        let mut upsert_sql = format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            quote_identifier(table_name),
+            columns.join(", "),
+            vec!["?"; columns.len()].join(", "),
+        );
+        if !key.is_empty() {
+            let key_columns: Vec<String> = key.iter().map(|col| quote_identifier(col)).collect();
+            let updates: Vec<String> = columns.iter().map(|col| format!("{col} = excluded.{col}", col = col)).collect();
+            upsert_sql.push_str(&format!(" ON CONFLICT ({}) DO UPDATE SET {}", key_columns.join(", "), updates.join(", ")));
+        }
+        upsert_sql
+    }
+    /// `CREATE TABLE` for `column_names`, an untyped column takes any value.
+    fn get_create_statement(&self, table_name: &str, column_names: &[String], column_types: &[Option<SqlType>]) -> String {
+        inferred_create_statement(table_name, column_names, column_types)
+    }
+    /// Stores rows in a table whose layout is inferred from every row, or
+    /// checked against the layout of the table if it exists already. Rows
+    /// are stamped with the request, and its game id when they lack one, and
+    /// replace only the rows the same request stored.
+    fn create_table(&self, table_name: &str,  headers: &[Value], json_rows: &[Value]) -> NbaResult<()> {
+        let db_conn = self.get_db_connection();
+        let mut column_names = header_names(table_name, headers)?;
+        if json_rows.is_empty() {
+            return Ok(());
+        }
+        let request_url = self.get_request_url()?;
+        let mut stamps: Vec<(&str, Value)> = Vec::new();
+        if let Some(game_id) = query_param(&request_url, "GameID").filter(|_| !column_names.iter().any(|c| c == "game_id")) {
+            stamps.push(("game_id", game_id.into()));
         }
-        insert_stmt.push_str("); \n");
-        insert_stmt
-        
-    }
-    fn get_create_statement(&self, headers: &Vec<Value>, row: &Vec<Value>) -> String {
-        let mut create_inserts = String::from("id integer primary key, ");
-        for (pos, val) in row.iter().enumerate() {
-            let col_name = headers[pos].as_str().unwrap().to_lowercase();
-            if val.is_f64() {
-                let col_row = format!(" {} FLOAT", col_name);
-                create_inserts.push_str(&col_row);
-            }else if val.is_i64() {
-                let col_row = format!(" {} INTEGER", col_name);
-                create_inserts.push_str(&col_row);
-            }else {
-                let col_row = format!(" {} TEXT", col_name);
-                create_inserts.push_str(&col_row);
+        stamps.push((REQUEST_URL_COLUMN, request_url.clone().into()));
+        let stamped_rows = json_rows.iter().enumerate()
+            .map(|(pos, row)| {
+                let mut values = row_values(table_name, pos, row, column_names.len())?.clone();
+                values.extend(stamps.iter().map(|(_, val)| val.clone()));
+                Ok(Value::Array(values))
+            })
+            .collect::<NbaResult<Vec<Value>>>()?;
+        column_names.extend(stamps.iter().map(|(name, _)| name.to_string()));
+        let json_rows = &stamped_rows;
+
+        // One transaction and one prepared upsert for every row
+        let tx = db_conn.unchecked_transaction()?;
+        // Tables stored before the stamps existed get their columns now
+        let stored_columns = table_columns(&tx, table_name)?;
+        if !stored_columns.is_empty() {
+            for (name, val) in &stamps {
+                if !stored_columns.iter().any(|(stored, _)| stored.eq_ignore_ascii_case(name)) {
+                    let column_type = SqlType::of(val).map_or("", |t| t.sql());
+                    tx.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", quote_identifier(table_name), quote_identifier(name), column_type), [])?;
+                }
             }
-            if pos != row.len() - 1 {
-                create_inserts.push(',');
+        }
+        match existing_column_types(&tx, table_name, &column_names)? {
+            Some(column_types) => check_column_types(table_name, &column_names, &column_types, json_rows)?,
+            None => {
+                let column_types = infer_column_types(table_name, &column_names, json_rows)?;
+                tx.execute(&self.get_create_statement(table_name, &column_names, &column_types), [])?;
             }
         }
-        create_inserts.push_str("); \n");
-        create_inserts
+        tx.execute(&format!("DELETE FROM {} WHERE {} = ?", quote_identifier(table_name), REQUEST_URL_COLUMN), params![request_url])?;
+        self.upsert_rows(&tx, table_name, &column_names, &[], json_rows)?;
+        tx.commit()?;
 
+        Ok(())
     }
-    fn create_table(&self, table_name: &str,  headers: &Vec<Value>, json_rows: &Vec<Value>) -> Result<()> {
-        let db_conn = self.get_db_connection();
-        if self.check_table_exists(table_name)? {
-            return Ok(());
+    /// Stores a result set in a table laid out by `schema`, creating it if
+    /// needed. Rows are matched to stored ones on the schema's key.
+    fn save_declared_rows(&self, schema: &TableSchema, table_name: &str, data_set: &ResultSet) -> NbaResult<()> {
+        let column_names = header_names(data_set.name, data_set.headers)?;
+        let mut column_types = Vec::with_capacity(column_names.len());
+        for column_name in &column_names {
+            let column_type = schema.column_type(column_name)
+                .ok_or_else(|| NbaError::SchemaMismatch(format!("{} has a column {} not declared for table {}", data_set.name, column_name, schema.name)))?;
+            column_types.push(Some(column_type));
         }
-        // let cleanup = format!("DROP TABLE IF EXISTS {}", table_name);
-        // db_conn.execute(&cleanup, [])?;
-        let mut create_sql = format!("CREATE TABLE IF NOT EXISTS {} ( ", table_name);
+        check_column_types(table_name, &column_names, &column_types, data_set.rows)?;
+        let tx = self.get_db_connection().unchecked_transaction()?;
+        schema.create(&tx, table_name)?;
+        self.upsert_rows(&tx, table_name, &column_names, schema.key, data_set.rows)?;
+        tx.commit()?;
+        Ok(())
+    }
+    fn upsert_rows(&self, conn: &Connection, table_name: &str, column_names: &[String], key: &[&str], json_rows: &[Value]) -> NbaResult<()> {
+        let mut upsert_stmt = conn.prepare(&self.get_upsert_row_statement(table_name, column_names, key))?;
         for (pos, row) in json_rows.iter().enumerate() {
-            let row_array = row.as_array().unwrap();
-            if pos == 0 {
-                let create_stmt = self.get_create_statement(headers, row_array);
-                create_sql.push_str(&create_stmt);
-            }
-            let insert_stmt = self.get_insert_row_statement(table_name, row_array);
-            create_sql.push_str(&insert_stmt);
+            let row_array = row_values(table_name, pos, row, column_names.len())?;
+            upsert_stmt.execute(params_from_iter(row_array.iter().map(json_to_sql)))?;
         }
-
-        db_conn.execute_batch(
-            &create_sql,
-        )?;
-
         Ok(())
     }
-    fn save_to_db_file(&self) -> Result<()> {
-        let endpoint_json = self.send_request().unwrap();
+    /// Stores one result set, in its registered table or else in one
+    /// inferred from its rows named after the endpoint and the result set,
+    /// so it never lands in a declared table. Returns the table, if it was
+    /// stored at all.
+    fn store_result_set(&self, data_set: &ResultSet) -> NbaResult<Option<String>> {
+        match registered_table(self.get_endpoint_name(), data_set.name) {
+            Some(schema) => {
+                self.save_declared_rows(schema, schema.name, data_set)?;
+                Ok(Some(schema.name.to_string()))
+            }
+            None => {
+                let table_name = inferred_table_name(self.get_endpoint_name(), data_set.name);
+                self.create_table(&table_name, data_set.headers, data_set.rows)?;
+                Ok(Some(table_name))
+            }
+        }
+    }
+    /// Stores every result set of an already fetched response and notes
+    /// where and when it came from.
+    fn save_json_to_db(&self, endpoint_json: &Value) -> NbaResult<()> {
         let load_start = Instant::now();
-        let result_sets = endpoint_json["resultSets"].as_array().unwrap();
-        for data_set in result_sets {
-            let data_set_values = data_set["rowSet"].as_array().unwrap();
-            let data_set_headers = data_set["headers"].as_array().unwrap();
-            let data_set_name = data_set["name"].as_str().unwrap().to_lowercase();
-            self.create_table(&data_set_name, data_set_headers, data_set_values)?;
+        let request_url = self.get_request_url()?;
+        for data_set in result_sets(endpoint_json)? {
+            if let Some(table_name) = self.store_result_set(&data_set)? {
+                self.get_warehouse().record_fetch(&request_url, &table_name, data_set.rows.len())?;
+            }
         }
         let sql_load_duration = load_start.elapsed();
-        println!("sql loading took {:?}", &sql_load_duration);
+        eprintln!("sql loading took {:?}", &sql_load_duration);
         Ok(())
     }
+    /// Fetches and stores the endpoint, unless the warehouse's copy is
+    /// younger than its max age.
+    fn save_to_db_file(&self) -> NbaResult<()> {
+        if self.get_warehouse().is_fresh(&self.get_request_url()?)? {
+            return Ok(());
+        }
+        let endpoint_json = self.send_request()?;
+        self.save_json_to_db(&endpoint_json)
+    }
 }
 
 pub trait SaveToDataframe: NBAEndpoint {
-    fn load_dataframes(&self) -> Result<HashMap<String, DataFrame>> {
-        let endpoint_json = self.send_request().unwrap();
+    fn load_dataframes(&self) -> NbaResult<HashMap<String, DataFrame>> {
+        let endpoint_json = self.send_request()?;
+        self.dataframes_from_json(&endpoint_json)
+    }
+    /// Builds one dataframe per result set of an already fetched response.
+    /// Columns come in header order, typed like the warehouse would store
+    /// them, with nulls kept and strings as they were sent.
+    fn dataframes_from_json(&self, endpoint_json: &Value) -> NbaResult<HashMap<String, DataFrame>> {
         let load_start = Instant::now();
-        let result_sets = endpoint_json["resultSets"].as_array().unwrap();
         let mut stats_dataframes: HashMap<String, DataFrame> = HashMap::new();
-        for data_set in result_sets {
-            let data_set_values = data_set["rowSet"].as_array().unwrap();
-            let data_set_headers = data_set["headers"].as_array().unwrap();
-            let mut headers_to_values: HashMap<&str, Vec<&Value>> = HashMap::new();
-            data_set_values.iter().for_each(|r| insert_row_values(&mut headers_to_values, &r, data_set_headers));
-    
-            let mut df_series: Vec<Series> = Vec::new();
-            for (col_name, json_values) in headers_to_values {
-                if json_values.is_empty() { continue; }
-                if let Some(first_non_null_val) = json_values.iter().find(|&v| !v.is_null()) {
-                    if first_non_null_val.is_i64() {
-                        let typed_data = json_values.iter().map(|&v| v.as_i64().unwrap_or(0)).collect::<Vec<i64>>();
-                        df_series.push(Series::new(col_name, typed_data));
-                    } else if first_non_null_val.is_f64()  {
-                        let typed_data = json_values.iter().map(|&v| v.as_f64().unwrap_or(0.0)).collect::<Vec<f64>>();
-                        df_series.push(Series::new(col_name, typed_data));
-                    } else {
-                        let typed_data = json_values.iter().map(|&v| v.as_str().unwrap_or("").to_lowercase()).collect::<Vec<String>>();
-                        df_series.push(Series::new(col_name, typed_data));
-                    }
-                }else {
-                    let v: Vec<&str> = vec![];
-                    df_series.push(Series::new(col_name, v));
-                }
-    
-            }
-            let data_set_name = data_set["name"].as_str().unwrap();
-            stats_dataframes.insert(data_set_name.to_string(), DataFrame::new(df_series)?);
+        for data_set in result_sets(endpoint_json)? {
+            let column_names = data_set.headers.iter()
+                .map(|header| header.as_str()
+                    .map(|h| h.to_string())
+                    .ok_or_else(|| NbaError::SchemaMismatch(format!("{} has a header that is not a string", data_set.name))))
+                .collect::<NbaResult<Vec<String>>>()?;
+            let column_types = infer_column_types(data_set.name, &column_names, data_set.rows)?;
+            stats_dataframes.insert(data_set.name.to_string(), dataframe_from_rows(&column_names, &column_types, data_set.rows)?);
         }
         let df_load_duration = load_start.elapsed();
-        println!("datafram loading took {:?}", &df_load_duration);
+        eprintln!("dataframe loading took {:?}", &df_load_duration);
         Ok(stats_dataframes)
     }
 }
 
-fn insert_row_values<'a>(headers_to_values: &mut HashMap<&'a str, Vec<&'a Value>>, row: &'a Value, headers: &'a Vec<Value>) -> () {
-    let row_array = row.as_array().unwrap();
-    for (pos, col_val) in row_array.iter().enumerate() {
-        let col_name = headers[pos].as_str().unwrap();
-        if let Some(series_values) = headers_to_values.get_mut(col_name) {
-            series_values.push(col_val);
-        }else {
-            headers_to_values.insert(col_name, Vec::new());
+/// Creates a table laid out by `column_names` and `column_types`, the way a
+/// result set without a registered table is stored.
+pub fn inferred_create_statement(table_name: &str, column_names: &[String], column_types: &[Option<SqlType>]) -> String {
+    let mut column_defs = vec!["id INTEGER PRIMARY KEY".to_string()];
+    for (column_name, column_type) in column_names.iter().zip(column_types) {
+        let column_def = match column_type {
+            Some(column_type) => format!("{} {}", quote_identifier(column_name), column_type.sql()),
+            None => quote_identifier(column_name),
+        };
+        column_defs.push(column_def);
+    }
+    format!("CREATE TABLE IF NOT EXISTS {} ({})", quote_identifier(table_name), column_defs.join(", "))
+}
+
+/// Table a result set without a registered table is stored in.
+pub fn inferred_table_name(endpoint: &str, result_set: &str) -> String {
+    format!("{}_{}", endpoint.to_lowercase(), result_set.to_lowercase())
+}
+
+/// Value of `param` in the query string of `url`.
+pub fn query_param(url: &str, param: &str) -> Option<String> {
+    let (_, query) = url.split_once('?')?;
+    query.split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(name, _)| name.eq_ignore_ascii_case(param))
+        .map(|(_, val)| val.to_string())
+        .filter(|val| !val.is_empty())
+}
+
+/// Quotes a table or column name so it can go into a statement whatever it
+/// contains.
+pub fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// The sqlite value stored for a json value, nested values are kept as json.
+pub fn json_to_sql(val: &Value) -> SqlValue {
+    match val {
+        Value::Null => SqlValue::Null,
+        Value::Bool(b) => SqlValue::Integer(*b as i64),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => SqlValue::Integer(i),
+            None => SqlValue::Real(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        Value::String(s) => SqlValue::Text(s.clone()),
+        nested => SqlValue::Text(nested.to_string()),
+    }
+}
+
+/// One entry of a response's `resultSets` array.
+pub struct ResultSet<'a> {
+    pub name: &'a str,
+    pub headers: &'a [Value],
+    pub rows: &'a [Value],
+}
+
+impl ResultSet<'_> {
+    /// Deserializes every row into `T`, each row is seen as an object keyed
+    /// by the result set headers so `T` can use `#[serde(rename = "HEADER")]`.
+    pub fn deserialize_rows<T: DeserializeOwned>(&self) -> NbaResult<Vec<T>> {
+        let mut typed_rows = Vec::with_capacity(self.rows.len());
+        for row in self.rows {
+            let row_array = row.as_array().ok_or_else(|| NbaError::SchemaMismatch("rowSet entry is not an array".to_string()))?;
+            if row_array.len() != self.headers.len() {
+                return Err(NbaError::SchemaMismatch(format!("row of {} has {} values but {} headers", self.name, row_array.len(), self.headers.len())));
+            }
+            let mut row_object = serde_json::Map::with_capacity(row_array.len());
+            for (header, col_val) in self.headers.iter().zip(row_array) {
+                let col_name = header.as_str().ok_or_else(|| NbaError::SchemaMismatch(format!("header of {} is not a string", self.name)))?;
+                row_object.insert(col_name.to_string(), col_val.clone());
+            }
+            let typed_row = serde_json::from_value(Value::Object(row_object))
+                .map_err(|e| NbaError::SchemaMismatch(format!("row of {}: {}", self.name, e)))?;
+            typed_rows.push(typed_row);
         }
-        
+        Ok(typed_rows)
     }
 }
 
-// Deprecated dataframe video loading
-// pub fn save_video(&self) -> Result<()> {
-//     if self.player_id.is_none() || self.keyword.is_none() {
-//         return Ok(());
-//     }
-//     let df_load_start = Instant::now();
-//     let playbyplay_frames = self.load_dataframes().unwrap();
-//     let playbyplay_df = playbyplay_frames.get("PlayByPlay").unwrap();
-//     let pid = self.player_id.as_ref().unwrap();
-//     let keyword = self.keyword.as_ref().unwrap();
-
-//     let filtered_df = filter_on_description(&playbyplay_df, &keyword, *pid)?;
-//     print_df(&filtered_df);
-    
-//     let video_avail_flag = filtered_df.column("VIDEO_AVAILABLE_FLAG")?;
-//     let video_mask = video_avail_flag.eq(1 as i32);
-//     let video_df = filtered_df.filter(&video_mask)?;
-
-//     let play_event_nums = video_df.column("EVENTNUM")?;
-//     let video_list_file_name = "video_list.txt";
-//     let mut video_list_file = std::fs::File::create(video_list_file_name)?;
-//     let game_str = self.game_id.to_string();
-//     let game_id_str = game_str.split("=").last().unwrap();
-//     let video_id = format!("{}_{}_{}", keyword, game_id_str, pid).replace(" ", "");
-//     let df_load_duration = df_load_start.elapsed();
-//     let video_start_time = Instant::now();
-//     for event_num_option in play_event_nums.i64()? {
-//         if let Some(event_num) = event_num_option {
-//             let video_url = get_url_for_video(&self.game_id.to_string(), &event_num.to_string())?;
-//             let video_file_name = format!("play_videos/{}_{}.mp4", &video_id, event_num);
-//             save_video(&video_url, &video_file_name)?;
-//             video_list_file.write(format!("file {}\n", &video_file_name).as_bytes())?;
-//         }
-        
-//     }
-//     let video_save_duration = video_start_time.elapsed();
-//     let output_file = format!("ALL_{}.mp4", video_id);
-//     let video_combine_start = Instant::now();
-//     combine_videos(video_list_file_name, &output_file);
-//     let video_combine_duration = video_combine_start.elapsed();
-//     println!("Loading game df took {:?}", df_load_duration);
-//     println!("Saving videos took {:?}", video_save_duration);
-//     println!("Combining videos took {:?}", video_combine_duration);
-//     Ok(())
-// }
\ No newline at end of file
+/// Pulls the `resultSets` out of a stats.nba.com response, checking each has
+/// the name/headers/rowSet layout the rest of the module relies on.
+pub fn result_sets(endpoint_json: &Value) -> NbaResult<Vec<ResultSet<'_>>> {
+    let result_sets = endpoint_json["resultSets"].as_array().ok_or_else(|| missing("resultSets"))?;
+    let mut parsed_sets = Vec::with_capacity(result_sets.len());
+    for data_set in result_sets {
+        let name = data_set["name"].as_str().ok_or_else(|| missing("resultSets[].name"))?;
+        let headers = data_set["headers"].as_array()
+            .ok_or_else(|| missing(&format!("headers of result set {}", name)))?;
+        let rows = data_set["rowSet"].as_array()
+            .ok_or_else(|| missing(&format!("rowSet of result set {}", name)))?;
+        parsed_sets.push(ResultSet { name, headers, rows });
+    }
+    Ok(parsed_sets)
+}
+
+/// A dataframe of json array rows, one column per name in order, typed as
+/// `column_types` says. Untyped columns are strings and nulls are kept.
+pub fn dataframe_from_rows(column_names: &[String], column_types: &[Option<SqlType>], rows: &[Value]) -> NbaResult<DataFrame> {
+    let mut df_series: Vec<Series> = Vec::with_capacity(column_names.len());
+    for (col, (col_name, column_type)) in column_names.iter().zip(column_types.iter().copied()).enumerate() {
+        let json_values = rows.iter().map(|row| &row[col]);
+        let series = match column_type {
+            Some(SqlType::Integer) => Series::new(col_name, json_values
+                .map(|v| v.as_i64().or_else(|| v.as_bool().map(i64::from)))
+                .collect::<Vec<Option<i64>>>()),
+            Some(SqlType::Real) => Series::new(col_name, json_values
+                .map(|v| v.as_f64())
+                .collect::<Vec<Option<f64>>>()),
+            Some(SqlType::Text) | None => Series::new(col_name, json_values
+                .map(|v| match v {
+                    Value::Null => None,
+                    Value::String(s) => Some(s.clone()),
+                    nested => Some(nested.to_string()),
+                })
+                .collect::<Vec<Option<String>>>()),
+        };
+        df_series.push(series);
+    }
+    Ok(DataFrame::new(df_series)?)
+}
+
+/// Values of row `pos` of `table_name`, which has to hold `column_count` of them.
+pub fn row_values<'a>(table_name: &str, pos: usize, row: &'a Value, column_count: usize) -> NbaResult<&'a Vec<Value>> {
+    let row_array = row.as_array()
+        .ok_or_else(|| NbaError::SchemaMismatch(format!("{} row {} is not an array", table_name, pos)))?;
+    if row_array.len() != column_count {
+        return Err(NbaError::SchemaMismatch(format!("{} row {} has {} values but {} headers", table_name, pos, row_array.len(), column_count)));
+    }
+    Ok(row_array)
+}
+
+/// Lowercased headers of a result set, the column names it's stored under.
+fn header_names(data_set_name: &str, headers: &[Value]) -> NbaResult<Vec<String>> {
+    headers.iter()
+        .map(|header| header.as_str()
+            .map(|h| h.to_lowercase())
+            .ok_or_else(|| NbaError::SchemaMismatch(format!("{} has a header that is not a string", data_set_name))))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nba::endpoints::GenericEndpoint;
+    use crate::nba::transport::UreqTransport;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    fn player_awards(player_id: &str, warehouse: &Warehouse) -> GenericEndpoint {
+        let transport = Arc::new(UreqTransport::new("https://stats.nba.com/stats", "https://cdn.nba.com", None).unwrap());
+        GenericEndpoint::new("playerawards", vec![("PlayerID".to_string(), player_id.to_string())], transport, warehouse.clone()).unwrap()
+    }
+
+    fn awards(person_id: i64, descriptions: &[&str]) -> Value {
+        let rows: Vec<Value> = descriptions.iter().map(|description| json!([person_id, description, "2019-20"])).collect();
+        json!({"resultSets": [{"name": "PlayerAwards", "headers": ["PERSON_ID", "DESCRIPTION", "SEASON"], "rowSet": rows}]})
+    }
+
+    fn stored(warehouse: &Warehouse, person_id: i64) -> Vec<String> {
+        warehouse.connection()
+            .prepare("SELECT description FROM playerawards_playerawards WHERE person_id = ? ORDER BY description").unwrap()
+            .query_map(params![person_id], |r| r.get(0)).unwrap()
+            .collect::<rusqlite::Result<Vec<String>>>().unwrap()
+    }
+
+    #[test]
+    fn unregistered_result_sets_replace_the_rows_of_their_request() {
+        let warehouse = Warehouse::open(":memory:", None).unwrap();
+        let lebron = player_awards("2544", &warehouse);
+        let curry = player_awards("201939", &warehouse);
+        // Awards of one player share a person id and are all kept
+        lebron.save_json_to_db(&awards(2544, &["All-NBA", "All-Star"])).unwrap();
+        curry.save_json_to_db(&awards(201939, &["All-Star"])).unwrap();
+        assert_eq!(stored(&warehouse, 2544), ["All-NBA", "All-Star"]);
+
+        lebron.save_json_to_db(&awards(2544, &["All-Star"])).unwrap();
+        assert_eq!(stored(&warehouse, 2544), ["All-Star"]);
+        assert_eq!(stored(&warehouse, 201939), ["All-Star"]);
+    }
+}
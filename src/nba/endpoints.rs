@@ -1,109 +1,164 @@
 
 use crate::nba::params::*;
-use crate::nba::db::{SaveToDB, SaveToDataframe};
+use crate::nba::db::{quote_identifier, result_sets, ResultSet, SaveToDB, SaveToDataframe};
+use crate::nba::error::{missing, NbaError, NbaResult};
+use crate::nba::events::PlayByPlayEvent;
+use crate::nba::filter::EventFilter;
+use crate::nba::output::OutputTable;
+use crate::nba::live_data::{get_endpoint_metadata, scoreboard_from_json};
+use crate::nba::transport::{Transport, NBA_STATS_BASE_URL};
+use crate::nba::warehouse::{Warehouse, PLAYBYPLAY};
 
 use std::thread;
 use polars::prelude::*;
-use serde::{Serialize, Deserialize};
+use serde::Deserialize;
 use serde_json::Value;
-use std::{collections::HashMap, io::{Read, Write}, time::Instant, env};
-use anyhow::Result;
+use std::{collections::HashSet, future::Future, io::Write, sync::{Arc, Mutex}, time::Instant, env};
 use std::process::Command;
-use rusqlite::{Connection, params};
-
-const NBA_BASE_URL: &str = "https://stats.nba.com/stats";
+use rusqlite::{params_from_iter, types::Value as SqlValue};
 
 struct PlayDbInfo {
     eventnum: i64,
 }
 
-#[derive(Serialize, Deserialize)]
-struct PlayerInfo {
-    player_id: String,
-    full_name: String,
-    team_code: String,
-    years: String,
-}
-
-#[derive(Serialize, Deserialize)]
-struct PlayerInfoFile {
-    players: HashMap<String, PlayerInfo>,
-}
-
 pub struct PlayByPlayV2 {
     pub start_period: StartPeriod,
     pub end_period: EndPeriod,
     pub game_id: GameID,
-    pub player_id: Option<i64>,
-    pub keyword: Option<String>,
-    db_connection: Connection,
+    pub filter: EventFilter,
+    transport: Arc<dyn Transport>,
+    warehouse: Warehouse,
 }
 
 pub struct BoxScoreDefensive {
     pub game_id: GameID,
-    db_connection: Connection
+    transport: Arc<dyn Transport>,
+    warehouse: Warehouse,
 }
 
 pub struct VidForPlay {
     pub game_id: GameID,
-    pub game_event_id: String,
-    db_connection: Connection,
+    pub game_event_id: GameEventID,
+    transport: Arc<dyn Transport>,
+    warehouse: Warehouse,
 }
 
 pub struct CommonAllPlayers {
     pub league_id: LeagueID,
     pub season: Season,
-    db_connection: Connection,
+    transport: Arc<dyn Transport>,
+    warehouse: Warehouse,
+}
+
+/// Team game log of a season, two rows per game played.
+pub struct LeagueGameLog {
+    pub league_id: LeagueID,
+    pub season: Season,
+    pub season_type: SeasonType,
+    transport: Arc<dyn Transport>,
+    warehouse: Warehouse,
+}
+
+pub struct BoxScoreSummaryV2 {
+    pub game_id: GameID,
+    transport: Arc<dyn Transport>,
+    warehouse: Warehouse,
+}
+
+/// Every game of a day, played or not. Only read, never stored.
+pub struct ScoreboardV2 {
+    pub game_date: GameDate,
+    pub league_id: LeagueID,
+    transport: Arc<dyn Transport>,
+}
+
+/// A game of a season's game log.
+#[derive(Debug, Clone, Deserialize)]
+pub struct LoggedGame {
+    #[serde(rename = "GAME_ID")]
+    pub game_id: String,
+    #[serde(rename = "GAME_DATE")]
+    pub game_date: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct PlayerMatch {
+    pub display_first_last: String,
+    pub person_id: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct TeamMatch {
+    pub team_id: i64,
+    pub team_name: Option<String>,
+}
+
+/// Any endpoint described in `endpoints_v2.json`, called with its default
+/// params plus whatever the caller overrides.
+pub struct GenericEndpoint {
+    pub name: String,
+    pub url: String,
+    pub params: Vec<Box<dyn NBAParam>>,
+    transport: Arc<dyn Transport>,
+    warehouse: Warehouse,
 }
 
 
 impl SaveToDB for PlayByPlayV2 {
-    fn get_db_connection(&self) -> &Connection {
-        &self.db_connection
+    fn get_warehouse(&self) -> &Warehouse {
+        &self.warehouse
     }
-    fn save_to_db_file(&self) -> Result<()> {
-        let endpoint_json = self.send_request().unwrap();
-        let load_start = Instant::now();
-        let result_sets = endpoint_json["resultSets"].as_array().unwrap();
-        for data_set in result_sets {
-            let data_set_values = data_set["rowSet"].as_array().unwrap();
-            let data_set_headers = data_set["headers"].as_array().unwrap();
-            let data_set_name = data_set["name"].as_str().unwrap();
-            if data_set_name == "PlayByPlay" {
-                let game_str = match &self.game_id {
-                    GameID::ID(game) => game,
-                };
-                let table_name = format!("{data_set_name}_{game_str}");
-                self.create_table(&table_name, data_set_headers, data_set_values)?;
-            }
+    fn store_result_set(&self, data_set: &ResultSet) -> NbaResult<Option<String>> {
+        if data_set.name != "PlayByPlay" {
+            return Ok(None);
         }
-        let sql_load_duration = load_start.elapsed();
-        println!("sql loading took {:?}", &sql_load_duration);
-        Ok(())
+        self.save_declared_rows(&PLAYBYPLAY, PLAYBYPLAY.name, data_set)?;
+        Ok(Some(PLAYBYPLAY.name.to_string()))
     }
 }
 
 impl SaveToDB for CommonAllPlayers {
-    fn get_db_connection(&self) -> &Connection {
-        &self.db_connection
+    fn get_warehouse(&self) -> &Warehouse {
+        &self.warehouse
     }
 }
 
 impl SaveToDB for BoxScoreDefensive {
-    fn get_db_connection(&self) -> &Connection {
-        &self.db_connection
+    fn get_warehouse(&self) -> &Warehouse {
+        &self.warehouse
     }
 }
 
 impl SaveToDB for VidForPlay {
-    fn get_db_connection(&self) -> &Connection {
-        &self.db_connection
+    fn get_warehouse(&self) -> &Warehouse {
+        &self.warehouse
+    }
+}
+
+impl SaveToDB for LeagueGameLog {
+    fn get_warehouse(&self) -> &Warehouse {
+        &self.warehouse
+    }
+}
+
+impl SaveToDB for BoxScoreSummaryV2 {
+    fn get_warehouse(&self) -> &Warehouse {
+        &self.warehouse
+    }
+}
+
+impl SaveToDB for GenericEndpoint {
+    fn get_warehouse(&self) -> &Warehouse {
+        &self.warehouse
     }
 }
 
 impl SaveToDataframe for PlayByPlayV2 {
 }
 
+impl SaveToDataframe for GenericEndpoint {
+}
+
 impl SaveToDataframe for CommonAllPlayers {
 }
 
@@ -112,120 +167,270 @@ impl SaveToDataframe for BoxScoreDefensive {
 
 
 pub trait NBAEndpoint {
-    fn send_request(&self) -> Result<Value>;
+    fn get_transport(&self) -> &Arc<dyn Transport>;
+    /// Name of the stats endpoint, also what its result sets are registered under.
+    fn get_endpoint_name(&self) -> &str;
+    /// Full url of the request, query string included.
+    fn get_request_url(&self) -> NbaResult<String>;
+    fn send_request(&self) -> NbaResult<Value> {
+        let endpoint_json = self.get_transport().get_json(&self.get_request_url()?)?;
+        Ok(fold_result_set(endpoint_json))
+    }
+}
+
+/// Async counterparts of the blocking endpoint calls. The request itself runs
+/// on tokio's blocking pool so many endpoints can be in flight at once, e.g.
+/// every game of a night, while the blocking API stays as it is.
+pub trait AsyncNBAEndpoint: NBAEndpoint {
+    fn send_request_async(&self) -> impl Future<Output = NbaResult<Value>> + Send + 'static {
+        let request_url = self.get_request_url();
+        let transport = Arc::clone(self.get_transport());
+        async move {
+            let request_url = request_url?;
+            let endpoint_json = tokio::task::spawn_blocking(move || transport.get_json(&request_url))
+                .await
+                .map_err(|e| NbaError::Transport(format!("request task failed: {}", e)))??;
+            Ok(fold_result_set(endpoint_json))
+        }
+    }
+    fn save_to_db_file_async(&self) -> impl Future<Output = NbaResult<()>> + '_ where Self: SaveToDB {
+        async move {
+            if self.get_warehouse().is_fresh(&self.get_request_url()?)? {
+                return Ok(());
+            }
+            let endpoint_json = self.send_request_async().await?;
+            self.save_json_to_db(&endpoint_json)
+        }
+    }
+}
+
+impl<T: NBAEndpoint + ?Sized> AsyncNBAEndpoint for T {}
+
+// A handful of endpoints return a single `resultSet` object instead of
+// the usual `resultSets` array, fold those into the common layout
+fn fold_result_set(mut endpoint_json: Value) -> Value {
+    if endpoint_json.get("resultSets").is_none() {
+        if let Some(result_set) = endpoint_json.get("resultSet").cloned() {
+            endpoint_json["resultSets"] = Value::Array(vec![result_set]);
+        }
+    }
+    endpoint_json
+}
+
+fn stats_url(transport: &dyn Transport, endpoint: &str, query: &str) -> String {
+    with_query(format!("{}/{}", transport.stats_base_url(), endpoint), query)
+}
+
+/// `url` followed by `query`, if there is one.
+fn with_query(url: String, query: &str) -> String {
+    if query.is_empty() {
+        url
+    } else {
+        format!("{}?{}", url, query)
+    }
 }
 
 impl NBAEndpoint for CommonAllPlayers {
-    fn send_request(&self) -> Result<Value> {
-        let endpoint_url = format!("{}/commonallplayers?{}&{}&IsOnlyCurrentSeason=0", NBA_BASE_URL, self.league_id, self.season);
-        Ok(fetch_nba_json(endpoint_url))
+    fn get_transport(&self) -> &Arc<dyn Transport> {
+        &self.transport
+    }
+    fn get_endpoint_name(&self) -> &str {
+        "commonallplayers"
+    }
+    fn get_request_url(&self) -> NbaResult<String> {
+        let query = build_query([&self.league_id as &dyn NBAParam, &self.season, &IsOnlyCurrentSeason(false)])?;
+        Ok(stats_url(self.transport.as_ref(), self.get_endpoint_name(), &query))
     }
 }
 
 impl NBAEndpoint for BoxScoreDefensive {
-    fn send_request(&self) -> Result<Value> {
-        let endpoint_url = format!("{}/boxscoreplayertrackv2?{}", NBA_BASE_URL, self.game_id);
-        Ok(fetch_nba_json(endpoint_url))
+    fn get_transport(&self) -> &Arc<dyn Transport> {
+        &self.transport
+    }
+    fn get_endpoint_name(&self) -> &str {
+        "boxscoreplayertrackv2"
+    }
+    fn get_request_url(&self) -> NbaResult<String> {
+        let query = build_query([&self.game_id as &dyn NBAParam])?;
+        Ok(stats_url(self.transport.as_ref(), self.get_endpoint_name(), &query))
     }
 }
 
 impl NBAEndpoint for PlayByPlayV2 {
-    fn send_request(&self) -> Result<Value> {
-        let endpoint_url = format!("{}/playbyplayv2?{}&{}&{}", NBA_BASE_URL, self.game_id, self.end_period, self.start_period);
-        Ok(fetch_nba_json(endpoint_url))
+    fn get_transport(&self) -> &Arc<dyn Transport> {
+        &self.transport
+    }
+    fn get_endpoint_name(&self) -> &str {
+        "playbyplayv2"
+    }
+    fn get_request_url(&self) -> NbaResult<String> {
+        let query = build_query([&self.game_id as &dyn NBAParam, &self.end_period, &self.start_period])?;
+        Ok(stats_url(self.transport.as_ref(), self.get_endpoint_name(), &query))
+    }
+}
+
+impl NBAEndpoint for GenericEndpoint {
+    fn get_transport(&self) -> &Arc<dyn Transport> {
+        &self.transport
+    }
+    fn get_endpoint_name(&self) -> &str {
+        &self.name
+    }
+    fn get_request_url(&self) -> NbaResult<String> {
+        let query = build_query(self.params.iter().map(|p| p.as_ref()))?;
+        // Most metadata urls point at stats.nba.com, route those through the
+        // configured stats base url and leave the odd data.nba.com feed as is
+        match self.url.strip_prefix(NBA_STATS_BASE_URL) {
+            Some(endpoint) => Ok(stats_url(self.transport.as_ref(), endpoint.trim_start_matches('/'), &query)),
+            None => Ok(with_query(self.url.clone(), &query)),
+        }
+    }
+}
+
+impl NBAEndpoint for LeagueGameLog {
+    fn get_transport(&self) -> &Arc<dyn Transport> {
+        &self.transport
+    }
+    fn get_endpoint_name(&self) -> &str {
+        "leaguegamelog"
+    }
+    fn get_request_url(&self) -> NbaResult<String> {
+        let query = build_query([&self.league_id as &dyn NBAParam, &self.season, &self.season_type, &PlayerOrTeam::Team])?;
+        Ok(stats_url(self.transport.as_ref(), self.get_endpoint_name(), &query))
+    }
+}
+
+impl NBAEndpoint for BoxScoreSummaryV2 {
+    fn get_transport(&self) -> &Arc<dyn Transport> {
+        &self.transport
+    }
+    fn get_endpoint_name(&self) -> &str {
+        "boxscoresummaryv2"
+    }
+    fn get_request_url(&self) -> NbaResult<String> {
+        let query = build_query([&self.game_id as &dyn NBAParam])?;
+        Ok(stats_url(self.transport.as_ref(), self.get_endpoint_name(), &query))
+    }
+}
+
+impl NBAEndpoint for ScoreboardV2 {
+    fn get_transport(&self) -> &Arc<dyn Transport> {
+        &self.transport
+    }
+    fn get_endpoint_name(&self) -> &str {
+        "scoreboardV2"
+    }
+    fn get_request_url(&self) -> NbaResult<String> {
+        let query = build_query([&self.game_date as &dyn NBAParam, &self.league_id, &DayOffset(0)])?;
+        Ok(stats_url(self.transport.as_ref(), self.get_endpoint_name(), &query))
     }
 }
 
 impl NBAEndpoint for VidForPlay {
-    fn send_request(&self) -> Result<Value> {
-        let endpoint_url = format!("{}/videoeventsasset?{}&GameEventID={}", NBA_BASE_URL, self.game_id, self.game_event_id);
-        Ok(fetch_nba_json(endpoint_url))
+    fn get_transport(&self) -> &Arc<dyn Transport> {
+        &self.transport
+    }
+    fn get_endpoint_name(&self) -> &str {
+        "videoeventsasset"
+    }
+    fn get_request_url(&self) -> NbaResult<String> {
+        let query = build_query([&self.game_id as &dyn NBAParam, &self.game_event_id])?;
+        Ok(stats_url(self.transport.as_ref(), self.get_endpoint_name(), &query))
     }
 }
 
 impl BoxScoreDefensive {
-    pub fn new(game_id: GameID) -> BoxScoreDefensive {
-        let db_connection = Connection::open("nba_siren.db").unwrap();
-        BoxScoreDefensive {
+    pub fn new(game_id: GameID, transport: Arc<dyn Transport>, warehouse: Warehouse) -> NbaResult<BoxScoreDefensive> {
+        Ok(BoxScoreDefensive {
             game_id,
-            db_connection
-        }
+            transport,
+            warehouse
+        })
     }
 }
 
 impl VidForPlay {
-    pub fn new(game_id: GameID, game_event_id: String) -> VidForPlay {
-        let db_connection = Connection::open("nba_siren.db").unwrap();
-        VidForPlay {
+    pub fn new(game_id: GameID, game_event_id: GameEventID, transport: Arc<dyn Transport>, warehouse: Warehouse) -> NbaResult<VidForPlay> {
+        Ok(VidForPlay {
             game_id,
             game_event_id,
-            db_connection
-        }
+            transport,
+            warehouse
+        })
     }
-    pub fn get_video_url(&self) -> Result<String> {
+    pub fn get_video_url(&self) -> NbaResult<String> {
         let video_detail_json = self.send_request()?;
-        let video_url_json = video_detail_json["resultSets"]["Meta"]["videoUrls"].as_array().unwrap();
-        let url = video_url_json[0]["lurl"].as_str().unwrap();
-        let u = url.to_string();
-        save_video(url, "test_video")?;
-        return Ok(u)
+        let url = video_url_from_json(&video_detail_json)?;
+        save_video(self.transport.as_ref(), &url, "test_video")?;
+        Ok(url)
     }
 }
 
 impl PlayByPlayV2 {
     pub fn new(start_period: StartPeriod, end_period: EndPeriod,
         game_id: GameID,
-        player_id: Option<i64>,
-        keyword: Option<String>,) -> PlayByPlayV2 {
-        let db_connection = Connection::open("playbyplay.db").unwrap();
-        PlayByPlayV2 {
+        filter: EventFilter,
+        transport: Arc<dyn Transport>,
+        warehouse: Warehouse) -> NbaResult<PlayByPlayV2> {
+        Ok(PlayByPlayV2 {
             start_period,
             end_period,
             game_id,
-            player_id,
-            keyword,
-            db_connection
-        }
+            filter,
+            transport,
+            warehouse
+        })
     }
-    pub fn print_play_by_play(&self) -> Result<()> {
-        let playbyplay_frames = self.load_dataframes().unwrap();
-        let playbyplay_df = playbyplay_frames.get("PlayByPlay").unwrap();
-        let player_id_col = playbyplay_df.column("PLAYER1_ID")?;
-        if let Some(p) = self.player_id {
-            let mask = player_id_col.eq(p);
-            let filtered_df = playbyplay_df.filter(&mask).unwrap();
-            print_df(&filtered_df);
-        }else {
-            print_df(playbyplay_df);
-        }        
-        Ok(())
+    /// Every event of the game as typed rows.
+    pub fn events(&self) -> NbaResult<Vec<PlayByPlayEvent>> {
+        events_from_json(&self.send_request()?)
     }
-    pub fn save_video_db(&self) -> Result<()> {
-        if self.player_id.is_none() || self.keyword.is_none() {
-            return Ok(());
+    /// The PlayByPlay result set, only the rows matching the filter.
+    pub fn play_by_play_table(&self) -> NbaResult<OutputTable> {
+        let playbyplay_json = self.send_request()?;
+        let playbyplay_frames = self.dataframes_from_json(&playbyplay_json)?;
+        let playbyplay_df = playbyplay_frames.get("PlayByPlay").ok_or_else(|| missing("PlayByPlay result set"))?;
+        if self.filter.is_empty() {
+            return Ok(OutputTable::from_dataframe("PlayByPlay", playbyplay_df));
+        }
+        // Matching is done on the typed events, the dataframe only keeps
+        // the rows whose EVENTNUM matched
+        let events = events_from_json(&playbyplay_json)?;
+        let matching_events: HashSet<i64> = self.filter.apply(&events)
+            .iter()
+            .map(|event| event.event_num)
+            .collect();
+        let mask: BooleanChunked = playbyplay_df.column("EVENTNUM")?
+            .i64()?
+            .into_iter()
+            .map(|event_num| event_num.is_some_and(|n| matching_events.contains(&n)))
+            .collect();
+        Ok(OutputTable::from_dataframe("PlayByPlay", &playbyplay_df.filter(&mask)?))
+    }
+    /// Downloads every matching clip with at most `video_workers` downloads
+    /// in flight, then stitches them into one file with ffmpeg.
+    pub fn save_video_db(&self, video_workers: usize) -> NbaResult<()> {
+        if self.filter.is_empty() {
+            return Err(NbaError::InvalidParam("saving videos needs a filter, a player id or an event kind".to_string()));
         }
         self.save_to_db_file()?;
         let conn = self.get_db_connection();
         let load_start = Instant::now();
-        let pid = self.player_id.as_ref().unwrap().to_string();
-        let keyword = self.keyword.as_ref().unwrap();
-        let game_str = self.game_id.to_string();
-        let game_id_str = match &self.game_id {
-            GameID::ID(game) => game,
-        };
-        let sql_keyword = format!("%{}%", keyword);
-        let table_name = format!("playbyplay_{game_id_str}");
-        let mut stmt = conn.prepare(
-            "SELECT game_id, eventnum FROM ?1 
-            WHERE game_id = ?2
-            AND video_available_flag=1 
-            AND player1_id = ?3
-            AND (homedescription LIKE ?4
-            OR neutraldescription LIKE ?4 
-            OR visitordescription LIKE ?4 );",
+        let GameID::ID(game_id_str) = &self.game_id;
+        // The margin and team conditions look at other rows of the table so
+        // its name goes into the query, the filter's values are all bound
+        let (filter_condition, filter_values) = self.filter.sql_condition(PLAYBYPLAY.name);
+        let mut stmt = conn.prepare(&format!(
+            "SELECT e.game_id, e.eventnum FROM {} AS e
+            WHERE e.game_id = ?
+            AND e.video_available_flag=1 
+            AND ({})
+            ORDER BY e.eventnum;", quote_identifier(PLAYBYPLAY.name), filter_condition),
         )?;
+        let mut query_values = vec![SqlValue::Text(game_id_str.clone())];
+        query_values.extend(filter_values);
 
-        let player_info_rows  = stmt.query_map::<PlayDbInfo, _, _>(params![table_name, game_id_str, &pid, sql_keyword], |row|{
+        let player_info_rows  = stmt.query_map::<PlayDbInfo, _, _>(params_from_iter(query_values), |row|{
             Ok(PlayDbInfo{
                 eventnum: row.get(1)?
             })
@@ -235,31 +440,52 @@ impl PlayByPlayV2 {
         let video_list_file_name = "video_list.txt";
         let mut video_list_file = std::fs::File::create(video_list_file_name)?;
 
-        let video_id = format!("{}_{}_{}", keyword, game_id_str, pid).replace(" ", "");
+        let filter_name: String = self.filter.to_string().chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        let video_id = format!("{}_{}", game_id_str, filter_name);
         let df_load_duration = load_start.elapsed();
         let video_start_time = Instant::now();
-        let mut save_vid_handles = Vec::new();
+        std::fs::create_dir_all("play_videos")?;
+        let mut video_jobs = Vec::new();
         for r in player_info_rows {
-            let res = r.unwrap();
-            let event_num = res.eventnum.to_string();
-            let video_url = get_url_for_video(&game_str, &event_num)?;
-            let video_file_name = format!("play_videos/{}_{}.mp4", &video_id, event_num);
-            video_list_file.write(format!("file {}\n", &video_file_name).as_bytes())?;
-            let save_video_thread = thread::spawn(move || {
-                save_video(&video_url, &video_file_name)
+            let res = r?;
+            let video_file_name = format!("play_videos/{}_{}.mp4", &video_id, res.eventnum);
+            video_list_file.write_all(format!("file {}\n", &video_file_name).as_bytes())?;
+            video_jobs.push((GameEventID::ID(res.eventnum), video_file_name));
+        }
+
+        // Workers pull clips off a shared queue, the transport's rate limiter
+        // keeps the video url lookups from flooding stats.nba.com
+        let video_queue = Arc::new(Mutex::new(video_jobs));
+        let mut save_vid_handles = Vec::new();
+        for _ in 0..video_workers.max(1) {
+            let video_queue = Arc::clone(&video_queue);
+            let transport = Arc::clone(&self.transport);
+            let game_id = self.game_id.clone();
+            let save_video_thread = thread::spawn(move || -> NbaResult<()> {
+                loop {
+                    let next_job = video_queue.lock().unwrap_or_else(|e| e.into_inner()).pop();
+                    let (game_event_id, video_file_name) = match next_job {
+                        Some(job) => job,
+                        None => return Ok(()),
+                    };
+                    let video_url = get_url_for_video(transport.as_ref(), &game_id, &game_event_id)?;
+                    save_video(transport.as_ref(), &video_url, &video_file_name)?;
+                }
             });
             save_vid_handles.push(save_video_thread);
         }
 
         for handle in save_vid_handles {
-            handle.join().unwrap()?;
+            handle.join().map_err(|_| NbaError::Video("video download thread panicked".to_string()))??;
         }
 
 
         let video_save_duration = video_start_time.elapsed();
         let output_file = format!("ALL_{}.mp4", video_id);
         let video_combine_start = Instant::now();
-        combine_videos(video_list_file_name, &output_file);
+        combine_videos(video_list_file_name, &output_file)?;
         let video_combine_duration = video_combine_start.elapsed();
         println!("Loading game data from sql took {:?}", df_load_duration);
         println!("Saving videos took {:?}", video_save_duration);
@@ -270,76 +496,209 @@ impl PlayByPlayV2 {
 }
 
 impl CommonAllPlayers {
-    pub fn new(league_id: LeagueID, season: Season) -> CommonAllPlayers {
-        let db_connection = Connection::open("all_players.db").unwrap();
-        CommonAllPlayers {
+    pub fn new(league_id: LeagueID, season: Season, transport: Arc<dyn Transport>, warehouse: Warehouse) -> NbaResult<CommonAllPlayers> {
+        Ok(CommonAllPlayers {
+            league_id,
+            season,
+            transport,
+            warehouse
+        })
+    }
+    /// Players whose name contains `name`, from the saved player list.
+    pub fn search_players(&self, name: &str) -> NbaResult<Vec<PlayerMatch>> {
+        self.search_table("commonallplayers", &["display_first_last", "person_id"], &["display_first_last"], name, |row| {
+            Ok(PlayerMatch {
+                display_first_last: row.get(0)?,
+                person_id: row.get(1)?,
+            })
+        })
+    }
+    /// Players of teams whose city, name or abbreviation contains `name`.
+    pub fn search_teams(&self, name: &str) -> NbaResult<Vec<TeamMatch>> {
+        self.search_table("commonallplayers", &["team_id", "team_name"], &["team_city", "team_name", "team_abbreviation", "team_code"], name, |row| {
+            Ok(TeamMatch {
+                team_id: row.get(0)?,
+                team_name: row.get(1)?,
+            })
+        })
+    }
+}
+
+impl LeagueGameLog {
+    pub fn new(league_id: LeagueID, season: Season, season_type: SeasonType, transport: Arc<dyn Transport>, warehouse: Warehouse) -> NbaResult<LeagueGameLog> {
+        Ok(LeagueGameLog {
             league_id,
             season,
-            db_connection
+            season_type,
+            transport,
+            warehouse
+        })
+    }
+    /// Fetches and stores the game log, returning every game once in the
+    /// order they were played.
+    pub fn games(&self) -> NbaResult<Vec<LoggedGame>> {
+        let game_log_json = self.send_request()?;
+        self.save_json_to_db(&game_log_json)?;
+        let mut games: Vec<LoggedGame> = result_sets(&game_log_json)?
+            .into_iter()
+            .find(|data_set| data_set.name == "LeagueGameLog")
+            .ok_or_else(|| missing("LeagueGameLog result set"))?
+            .deserialize_rows()?;
+        games.sort_by(|a, b| (&a.game_date, &a.game_id).cmp(&(&b.game_date, &b.game_id)));
+        games.dedup_by(|a, b| a.game_id == b.game_id);
+        Ok(games)
+    }
+}
+
+impl ScoreboardV2 {
+    pub fn new(game_date: GameDate, league_id: LeagueID, transport: Arc<dyn Transport>) -> NbaResult<ScoreboardV2> {
+        Ok(ScoreboardV2 {
+            game_date,
+            league_id,
+            transport
+        })
+    }
+    /// The day's games, one row per team like the live scoreboard.
+    pub fn scoreboard(&self) -> NbaResult<OutputTable> {
+        scoreboard_from_json(&self.send_request()?)
+    }
+}
+
+impl BoxScoreSummaryV2 {
+    pub fn new(game_id: GameID, transport: Arc<dyn Transport>, warehouse: Warehouse) -> NbaResult<BoxScoreSummaryV2> {
+        Ok(BoxScoreSummaryV2 {
+            game_id,
+            transport,
+            warehouse
+        })
+    }
+}
+
+impl GenericEndpoint {
+    /// Builds the endpoint from its metadata entry, `overrides` replace the
+    /// default value of a matching param (case insensitive) or are appended.
+    pub fn new(endpoint: &str, overrides: Vec<(String, String)>, transport: Arc<dyn Transport>, warehouse: Warehouse) -> NbaResult<GenericEndpoint> {
+        let endpoint_metadata = get_endpoint_metadata()?;
+        let (name, metadata) = endpoint_metadata.as_object()
+            .and_then(|endpoints| endpoints.iter().find(|(name, _)| name.eq_ignore_ascii_case(endpoint)))
+            .ok_or_else(|| NbaError::InvalidParam(format!("No info found for {}", endpoint)))?;
+        let url = metadata["url"].as_str()
+            .ok_or_else(|| missing(&format!("url of endpoint {}", name)))?
+            .to_string();
+        // Metadata defaults are stored form encoded, keep them raw until the url is built
+        let mut params: Vec<Box<dyn NBAParam>> = Vec::new();
+        let mut default_keys: Vec<String> = Vec::new();
+        if let Some(default_params) = metadata["params"].as_object() {
+            for (key, val) in default_params {
+                let raw_val = val.as_str().unwrap_or("").replace('+', " ");
+                default_keys.push(key.to_string());
+                params.push(Box::new(RawParam { key: key.to_string(), value: raw_val }));
+            }
+        }
+        for (key, val) in overrides {
+            let default_pos = default_keys.iter().position(|default_key| default_key.eq_ignore_ascii_case(&key));
+            let key = default_pos.map_or(key, |pos| default_keys[pos].clone());
+            let typed_param = parse_param(&key, &val)?;
+            match default_pos {
+                Some(pos) => params[pos] = typed_param,
+                None => params.push(typed_param),
+            }
         }
+        Ok(GenericEndpoint {
+            name: name.to_string(),
+            url,
+            params,
+            transport,
+            warehouse
+        })
     }
 }
 
 
-fn combine_videos(video_list_file_name: &str, output_file_name: &str) {
+/// Typed events of the PlayByPlay result set of a `playbyplayv2` response.
+pub fn events_from_json(endpoint_json: &Value) -> NbaResult<Vec<PlayByPlayEvent>> {
+    result_sets(endpoint_json)?
+        .into_iter()
+        .find(|data_set| data_set.name == "PlayByPlay")
+        .ok_or_else(|| missing("PlayByPlay result set"))?
+        .deserialize_rows()
+}
+
+fn combine_videos(video_list_file_name: &str, output_file_name: &str) -> NbaResult<()> {
     let mut ffmpeg_cmd = "./ffmpeg";
     if env::consts::OS == "windows" {
         ffmpeg_cmd = "./ffmpeg.exe";
     }
     let video_processor = Command::new(ffmpeg_cmd)
-        .args(["-f", "concat", "-i", video_list_file_name, "-c", "copy", output_file_name, "-y"]).output().unwrap();
-    println!("Saving {}, status: {}", video_processor.status, output_file_name);
+        .args(["-f", "concat", "-i", video_list_file_name, "-c", "copy", output_file_name, "-y"]).output()
+        .map_err(|e| NbaError::Ffmpeg(format!("could not run {}: {}", ffmpeg_cmd, e)))?;
+    println!("Saving {}, status: {}", output_file_name, video_processor.status);
     if !video_processor.status.success() {
-        let e = String::from_utf8(video_processor.stderr).unwrap();
-        println!("{}", e);
+        let e = String::from_utf8_lossy(&video_processor.stderr);
+        return Err(NbaError::Ffmpeg(e.to_string()));
     }
+    Ok(())
+}
+
+fn save_video(transport: &dyn Transport, video_url: &str, file_name: &str) -> NbaResult<()> {
+    let bytes = transport.get_bytes(video_url)?;
+    let mut f = std::fs::File::create(file_name)
+        .map_err(|e| NbaError::Video(format!("could not create {}: {}", file_name, e)))?;
+    f.write_all(&bytes)
+        .map_err(|e| NbaError::Video(format!("could not write {}: {}", file_name, e)))?;
+    Ok(())
 }
 
-fn print_df(df: &DataFrame) -> () {
-    let col_names = df.get_column_names();
-    let df_size = df.height();
-    for i in 0..df_size {
-        if let Some(row) = df.get(i) {
-            row.iter().enumerate().for_each(|(i, v)|  print!("{} : {}, ", col_names[i], v.to_string()))
+/// Assembles the `Key=Value&...` query string, validating every param on the way.
+pub fn build_query<'a>(params: impl IntoIterator<Item = &'a dyn NBAParam>) -> NbaResult<String> {
+    let mut query: Vec<String> = Vec::new();
+    for p in params {
+        let (key, value) = p.get_formatted_param()?;
+        query.push(format!("{}={}", key, encode_query_value(&value)));
+    }
+    Ok(query.join("&"))
+}
+
+fn encode_query_value(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for b in value.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(b as char),
+            b' ' => encoded.push('+'),
+            _ => encoded.push_str(&format!("%{:02X}", b)),
         }
-        print!("\n");
-    }
-}
-
-fn save_video(video_url: &str, file_name: &str) -> Result<()> {
-    let req = ureq::get(video_url).call().unwrap();
-    let len: usize = req.header("Content-Length")
-    .unwrap()
-    .parse().unwrap();
-    let mut bytes: Vec<u8> = Vec::with_capacity(len);
-    req.into_reader().read_to_end(&mut bytes).unwrap();
-    let mut f = std::fs::File::create(file_name).unwrap();
-    f.write_all(&bytes).unwrap();
-    Ok(())  
-}
-
-
-fn fetch_nba_json(endpoint_url:String) -> Value {
-    let r = ureq::get(&endpoint_url)
-    .set("Host","stats.nba.com")
-    .set("User-Agent","Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:72.0) Gecko/20100101 Firefox/72.0")
-    .set("Accept","application/json, text/plain, */*")
-    .set("Accept-Language","en-US,en;q=0.5")
-    .set("Accept-Encoding","gzip, deflate, br")
-    // .set("x-nba-stats-origin","stats")
-    // .set("x-nba-stats-token","true")
-    .set("Connection","keep-alive")
-    .set("Referer","https://stats.nba.com/")
-    .set("Pragma","no-cache")
-    .set("Cache-Control","no-cache")
-    .call().expect("Failed to fetch data from nba server");
-    r.into_json().expect("Failed to fetch data from nba server")
-}
-
-fn get_url_for_video(game_id: &str, game_event_id: &str) -> Result<String> {
-    let endpoint_url = format!("{}/videoeventsasset?{}&GameEventID={}", NBA_BASE_URL, game_id, game_event_id);
-    let video_detail_json = fetch_nba_json(endpoint_url);
-    let video_url_json = video_detail_json["resultSets"]["Meta"]["videoUrls"].as_array().unwrap();
-    let url = video_url_json[0]["lurl"].as_str().unwrap();
-    Ok(url.to_string())
+    }
+    encoded
+}
+
+fn get_url_for_video(transport: &dyn Transport, game_id: &GameID, game_event_id: &GameEventID) -> NbaResult<String> {
+    let query = build_query([game_id as &dyn NBAParam, game_event_id])?;
+    let video_detail_json = transport.get_stats_json("videoeventsasset", &query)?;
+    video_url_from_json(&video_detail_json)
+}
+
+fn video_url_from_json(video_detail_json: &Value) -> NbaResult<String> {
+    video_detail_json["resultSets"]["Meta"]["videoUrls"][0]["lurl"].as_str()
+        .map(|url| url.to_string())
+        .ok_or_else(|| NbaError::Video("no video available for this play".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nba::transport::UreqTransport;
+
+    fn endpoint(name: &str) -> GenericEndpoint {
+        let transport = Arc::new(UreqTransport::new("https://stats.nba.com/stats", "https://cdn.nba.com", None).unwrap());
+        GenericEndpoint::new(name, Vec::new(), transport, Warehouse::open(":memory:", None).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn urls_without_params_have_no_query() {
+        assert_eq!(
+            endpoint("00_full_schedule_week.json").get_request_url().unwrap(),
+            "https://data.nba.com/data/10s/v2015/json/mobile_teams/nba/2018/league/00_full_schedule_week.json",
+        );
+        assert_eq!(endpoint("playerawards").get_request_url().unwrap(), "https://stats.nba.com/stats/playerawards?PlayerID=");
+    }
 }
@@ -0,0 +1,121 @@
+use std::fmt;
+
+pub type NbaResult<T> = std::result::Result<T, NbaError>;
+
+/// Everything that can go wrong talking to nba.com or storing what came back.
+#[derive(Debug)]
+pub enum NbaError {
+    /// Connection refused, dns failure, timeout...
+    Transport(String),
+    HttpStatus { url: String, status: u16 },
+    /// stats.nba.com answered 429, `retry_after` is in seconds when the server says
+    RateLimited { url: String, retry_after: Option<u64> },
+    /// The response didn't contain a result set or key we rely on
+    MissingResultSet(String),
+    /// A value doesn't have the type or shape we expected
+    SchemaMismatch(String),
+    InvalidParam(String),
+    /// `--offline` was passed and the response was never cached
+    NotCached(String),
+    Database(rusqlite::Error),
+    Io(std::io::Error),
+    Video(String),
+    Ffmpeg(String),
+    /// An alert sink couldn't deliver
+    Alert(String),
+}
+
+impl NbaError {
+    /// Process exit code for the cli, stable so scripts can branch on it.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            NbaError::InvalidParam(_) => 2,
+            NbaError::Transport(_) => 10,
+            NbaError::HttpStatus { .. } => 11,
+            NbaError::RateLimited { .. } => 12,
+            NbaError::MissingResultSet(_) => 13,
+            NbaError::SchemaMismatch(_) => 14,
+            NbaError::NotCached(_) => 15,
+            NbaError::Database(_) => 20,
+            NbaError::Io(_) => 21,
+            NbaError::Video(_) => 30,
+            NbaError::Ffmpeg(_) => 31,
+            NbaError::Alert(_) => 40,
+        }
+    }
+}
+
+impl fmt::Display for NbaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NbaError::Transport(e) => write!(f, "could not reach nba.com: {}", e),
+            NbaError::HttpStatus { url, status } => write!(f, "{} answered with http status {}", url, status),
+            NbaError::RateLimited { url, retry_after: Some(s) } => write!(f, "rate limited by {}, retry after {}s", url, s),
+            NbaError::RateLimited { url, retry_after: None } => write!(f, "rate limited by {}", url),
+            NbaError::MissingResultSet(what) => write!(f, "response is missing {}", what),
+            NbaError::SchemaMismatch(e) => write!(f, "unexpected data: {}", e),
+            NbaError::InvalidParam(e) => write!(f, "invalid parameter: {}", e),
+            NbaError::NotCached(url) => write!(f, "offline and no cached response for {}", url),
+            NbaError::Database(e) => write!(f, "database error: {}", e),
+            NbaError::Io(e) => write!(f, "io error: {}", e),
+            NbaError::Video(e) => write!(f, "video error: {}", e),
+            NbaError::Ffmpeg(e) => write!(f, "ffmpeg failed: {}", e),
+            NbaError::Alert(e) => write!(f, "alert not delivered: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for NbaError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            NbaError::Database(e) => Some(e),
+            NbaError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<rusqlite::Error> for NbaError {
+    fn from(e: rusqlite::Error) -> Self {
+        NbaError::Database(e)
+    }
+}
+
+impl From<std::io::Error> for NbaError {
+    fn from(e: std::io::Error) -> Self {
+        NbaError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for NbaError {
+    fn from(e: serde_json::Error) -> Self {
+        NbaError::SchemaMismatch(e.to_string())
+    }
+}
+
+impl From<polars::prelude::PolarsError> for NbaError {
+    fn from(e: polars::prelude::PolarsError) -> Self {
+        NbaError::SchemaMismatch(e.to_string())
+    }
+}
+
+impl From<ureq::Error> for NbaError {
+    fn from(e: ureq::Error) -> Self {
+        match e {
+            ureq::Error::Status(429, response) => NbaError::RateLimited {
+                url: response.get_url().to_string(),
+                retry_after: response.header("Retry-After").and_then(|s| s.parse().ok()),
+            },
+            ureq::Error::Status(status, response) => NbaError::HttpStatus {
+                url: response.get_url().to_string(),
+                status,
+            },
+            ureq::Error::Transport(t) => NbaError::Transport(t.to_string()),
+        }
+    }
+}
+
+/// Shorthand for the common "this key should be in the response" check.
+pub fn missing(what: &str) -> NbaError {
+    NbaError::MissingResultSet(what.to_string())
+}
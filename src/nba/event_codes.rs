@@ -0,0 +1,319 @@
+use std::fmt;
+use serde::{Serialize, Serializer};
+
+// Declares an enum for one of nba.com's numeric code tables, every code not
+// listed decodes to `Unknown` so new codes don't break parsing.
+macro_rules! event_codes {
+    ($(#[$meta:meta])* $name:ident { $($variant:ident = $code:literal => $label:literal,)* }) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $($variant,)*
+            Unknown(i64),
+        }
+
+        impl $name {
+            pub const ALL: &'static [$name] = &[$($name::$variant,)*];
+
+            pub fn code(&self) -> i64 {
+                match self {
+                    $($name::$variant => $code,)*
+                    $name::Unknown(code) => *code,
+                }
+            }
+        }
+
+        impl From<i64> for $name {
+            fn from(code: i64) -> Self {
+                match code {
+                    $($code => $name::$variant,)*
+                    other => $name::Unknown(other),
+                }
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                match self {
+                    $($name::$variant => write!(f, $label),)*
+                    $name::Unknown(code) => write!(f, "unknown ({})", code),
+                }
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+    };
+}
+
+event_codes! {
+    /// `EVENTMSGTYPE`, what kind of play an event is.
+    EventMsgType {
+        MadeShot = 1 => "made shot",
+        MissedShot = 2 => "missed shot",
+        FreeThrow = 3 => "free throw",
+        Rebound = 4 => "rebound",
+        Turnover = 5 => "turnover",
+        Foul = 6 => "foul",
+        Violation = 7 => "violation",
+        Substitution = 8 => "substitution",
+        Timeout = 9 => "timeout",
+        JumpBall = 10 => "jump ball",
+        Ejection = 11 => "ejection",
+        PeriodStart = 12 => "period start",
+        PeriodEnd = 13 => "period end",
+        InstantReplay = 18 => "instant replay",
+        Stoppage = 20 => "stoppage",
+    }
+}
+
+event_codes! {
+    /// `EVENTMSGACTIONTYPE` of made and missed shots.
+    ShotType {
+        JumpShot = 1 => "jump shot",
+        RunningJumpShot = 2 => "running jump shot",
+        HookShot = 3 => "hook shot",
+        TipShot = 4 => "tip shot",
+        Layup = 5 => "layup",
+        DrivingLayup = 6 => "driving layup",
+        Dunk = 7 => "dunk",
+        SlamDunk = 8 => "slam dunk",
+        DrivingDunk = 9 => "driving dunk",
+        RunningLayup = 41 => "running layup",
+        AlleyOopLayup = 43 => "alley oop layup",
+        ReverseLayup = 44 => "reverse layup",
+        TurnaroundJumpShot = 47 => "turnaround jump shot",
+        RunningDunk = 50 => "running dunk",
+        ReverseDunk = 51 => "reverse dunk",
+        AlleyOopDunk = 52 => "alley oop dunk",
+        RunningHookShot = 56 => "running hook shot",
+        TurnaroundHookShot = 58 => "turnaround hook shot",
+        FadeawayJumpShot = 63 => "fadeaway jump shot",
+        JumpHookShot = 65 => "jump hook shot",
+        JumpBankShot = 66 => "jump bank shot",
+        HookBankShot = 67 => "hook bank shot",
+        FingerRollLayup = 71 => "finger roll layup",
+        PutbackLayup = 72 => "putback layup",
+        DrivingReverseLayup = 73 => "driving reverse layup",
+        RunningReverseLayup = 74 => "running reverse layup",
+        DrivingFingerRollLayup = 75 => "driving finger roll layup",
+        RunningFingerRollLayup = 76 => "running finger roll layup",
+        DrivingJumpShot = 77 => "driving jump shot",
+        FloatingJumpShot = 78 => "floating jump shot",
+        PullUpJumpShot = 79 => "pull-up jump shot",
+        StepBackJumpShot = 80 => "step back jump shot",
+        PullUpBankShot = 81 => "pull-up bank shot",
+        DrivingBankShot = 82 => "driving bank shot",
+        FadeawayBankShot = 83 => "fadeaway bank shot",
+        RunningBankShot = 84 => "running bank shot",
+        TurnaroundBankShot = 85 => "turnaround bank shot",
+        TurnaroundFadeawayShot = 86 => "turnaround fadeaway shot",
+        PutbackDunk = 87 => "putback dunk",
+        DrivingSlamDunk = 88 => "driving slam dunk",
+        ReverseSlamDunk = 89 => "reverse slam dunk",
+        RunningSlamDunk = 90 => "running slam dunk",
+        PutbackSlamDunk = 91 => "putback slam dunk",
+        DrivingHookShot = 93 => "driving hook shot",
+        TurnaroundBankHookShot = 96 => "turnaround bank hook shot",
+        TipLayup = 97 => "tip layup",
+        CuttingLayup = 98 => "cutting layup",
+        CuttingFingerRollLayup = 99 => "cutting finger roll layup",
+        RunningAlleyOopLayup = 100 => "running alley oop layup",
+        DrivingFloatingJumpShot = 101 => "driving floating jump shot",
+        DrivingFloatingBankJumpShot = 102 => "driving floating bank jump shot",
+        RunningPullUpJumpShot = 103 => "running pull-up jump shot",
+        StepBackBankJumpShot = 104 => "step back bank jump shot",
+        TurnaroundFadeawayBankJumpShot = 105 => "turnaround fadeaway bank jump shot",
+        RunningAlleyOopDunk = 106 => "running alley oop dunk",
+        TipDunk = 107 => "tip dunk",
+        CuttingDunk = 108 => "cutting dunk",
+        DrivingReverseDunk = 109 => "driving reverse dunk",
+        RunningReverseDunk = 110 => "running reverse dunk",
+    }
+}
+
+event_codes! {
+    /// `EVENTMSGACTIONTYPE` of free throws.
+    FreeThrowType {
+        OneOfOne = 10 => "free throw 1 of 1",
+        OneOfTwo = 11 => "free throw 1 of 2",
+        TwoOfTwo = 12 => "free throw 2 of 2",
+        OneOfThree = 13 => "free throw 1 of 3",
+        TwoOfThree = 14 => "free throw 2 of 3",
+        ThreeOfThree = 15 => "free throw 3 of 3",
+        Technical = 16 => "technical free throw",
+        FlagrantOneOfTwo = 18 => "flagrant free throw 1 of 2",
+        FlagrantTwoOfTwo = 19 => "flagrant free throw 2 of 2",
+        FlagrantOneOfOne = 20 => "flagrant free throw 1 of 1",
+        ClearPathOneOfTwo = 25 => "clear path free throw 1 of 2",
+        ClearPathTwoOfTwo = 26 => "clear path free throw 2 of 2",
+    }
+}
+
+event_codes! {
+    /// `EVENTMSGACTIONTYPE` of turnovers.
+    TurnoverType {
+        BadPass = 1 => "bad pass turnover",
+        LostBall = 2 => "lost ball turnover",
+        Traveling = 4 => "traveling",
+        DoubleDribble = 6 => "double dribble",
+        DiscontinuedDribble = 7 => "discontinued dribble",
+        ThreeSecondViolation = 8 => "3 second violation",
+        FiveSecondViolation = 9 => "5 second violation",
+        EightSecondViolation = 10 => "8 second violation",
+        ShotClock = 11 => "shot clock turnover",
+        InboundTurnover = 12 => "inbound turnover",
+        Backcourt = 13 => "backcourt turnover",
+        OffensiveGoaltending = 15 => "offensive goaltending",
+        LaneViolation = 17 => "lane violation turnover",
+        JumpBallViolation = 18 => "jump ball violation turnover",
+        KickedBall = 19 => "kicked ball turnover",
+        IllegalAssist = 20 => "illegal assist turnover",
+        Palming = 21 => "palming",
+        IllegalScreen = 36 => "illegal screen turnover",
+        OffensiveFoul = 37 => "offensive foul turnover",
+        StepOutOfBounds = 39 => "step out of bounds",
+        OutOfBoundsLostBall = 40 => "out of bounds lost ball",
+        PossessionLostBall = 41 => "possession lost ball",
+        TooManyPlayers = 44 => "too many players turnover",
+        OutOfBoundsBadPass = 45 => "out of bounds bad pass",
+    }
+}
+
+event_codes! {
+    /// `EVENTMSGACTIONTYPE` of fouls.
+    FoulType {
+        Personal = 1 => "personal foul",
+        Shooting = 2 => "shooting foul",
+        LooseBall = 3 => "loose ball foul",
+        Offensive = 4 => "offensive foul",
+        Inbound = 5 => "inbound foul",
+        AwayFromPlay = 6 => "away from play foul",
+        Punching = 8 => "punching foul",
+        ClearPath = 9 => "clear path foul",
+        Double = 10 => "double foul",
+        Technical = 11 => "technical foul",
+        NonUnsportsmanlikeTechnical = 12 => "non unsportsmanlike technical",
+        HangingTechnical = 13 => "hanging technical",
+        FlagrantOne = 14 => "flagrant foul type 1",
+        FlagrantTwo = 15 => "flagrant foul type 2",
+        DoubleTechnical = 16 => "double technical",
+        DefensiveThreeSeconds = 17 => "defensive 3 seconds",
+        DelayTechnical = 18 => "delay technical",
+        TauntingTechnical = 19 => "taunting technical",
+        ExcessTimeoutTechnical = 25 => "excess timeout technical",
+        OffensiveCharge = 26 => "offensive charge foul",
+        PersonalBlock = 27 => "personal block foul",
+        PersonalTake = 28 => "personal take foul",
+        ShootingBlock = 29 => "shooting block foul",
+        TooManyPlayersTechnical = 30 => "too many players technical",
+        TransitionTake = 31 => "transition take foul",
+    }
+}
+
+event_codes! {
+    /// `EVENTMSGACTIONTYPE` of violations.
+    ViolationType {
+        DelayOfGame = 1 => "delay of game",
+        DefensiveGoaltending = 2 => "defensive goaltending",
+        Lane = 3 => "lane violation",
+        JumpBall = 4 => "jump ball violation",
+        KickedBall = 5 => "kicked ball violation",
+        DoubleLane = 6 => "double lane violation",
+    }
+}
+
+event_codes! {
+    /// `EVENTMSGACTIONTYPE` of timeouts.
+    TimeoutType {
+        Regular = 1 => "regular timeout",
+        Short = 2 => "short timeout",
+        Official = 4 => "official timeout",
+    }
+}
+
+/// `EVENTMSGACTIONTYPE` decoded against the table of its `EVENTMSGTYPE`,
+/// the same action code means different things for different event types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventAction {
+    Shot(ShotType),
+    FreeThrow(FreeThrowType),
+    Turnover(TurnoverType),
+    Foul(FoulType),
+    Violation(ViolationType),
+    Timeout(TimeoutType),
+    /// Event types without an action table, rebounds, substitutions...
+    Other(i64),
+}
+
+impl EventAction {
+    pub fn decode(event_type: EventMsgType, action_code: i64) -> EventAction {
+        match event_type {
+            EventMsgType::MadeShot | EventMsgType::MissedShot => EventAction::Shot(action_code.into()),
+            EventMsgType::FreeThrow => EventAction::FreeThrow(action_code.into()),
+            EventMsgType::Turnover => EventAction::Turnover(action_code.into()),
+            EventMsgType::Foul => EventAction::Foul(action_code.into()),
+            EventMsgType::Violation => EventAction::Violation(action_code.into()),
+            EventMsgType::Timeout => EventAction::Timeout(action_code.into()),
+            _ => EventAction::Other(action_code),
+        }
+    }
+
+    /// Every action with a name, used to look actions up by name.
+    pub fn all() -> impl Iterator<Item = EventAction> {
+        ShotType::ALL.iter().map(|&a| EventAction::Shot(a))
+            .chain(FreeThrowType::ALL.iter().map(|&a| EventAction::FreeThrow(a)))
+            .chain(TurnoverType::ALL.iter().map(|&a| EventAction::Turnover(a)))
+            .chain(FoulType::ALL.iter().map(|&a| EventAction::Foul(a)))
+            .chain(ViolationType::ALL.iter().map(|&a| EventAction::Violation(a)))
+            .chain(TimeoutType::ALL.iter().map(|&a| EventAction::Timeout(a)))
+    }
+
+    pub fn code(&self) -> i64 {
+        match self {
+            EventAction::Shot(a) => a.code(),
+            EventAction::FreeThrow(a) => a.code(),
+            EventAction::Turnover(a) => a.code(),
+            EventAction::Foul(a) => a.code(),
+            EventAction::Violation(a) => a.code(),
+            EventAction::Timeout(a) => a.code(),
+            EventAction::Other(code) => *code,
+        }
+    }
+
+    /// Event types this action can appear on.
+    pub fn event_types(&self) -> &'static [EventMsgType] {
+        match self {
+            EventAction::Shot(_) => &[EventMsgType::MadeShot, EventMsgType::MissedShot],
+            EventAction::FreeThrow(_) => &[EventMsgType::FreeThrow],
+            EventAction::Turnover(_) => &[EventMsgType::Turnover],
+            EventAction::Foul(_) => &[EventMsgType::Foul],
+            EventAction::Violation(_) => &[EventMsgType::Violation],
+            EventAction::Timeout(_) => &[EventMsgType::Timeout],
+            EventAction::Other(_) => &[],
+        }
+    }
+}
+
+impl fmt::Display for EventAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EventAction::Shot(a) => a.fmt(f),
+            EventAction::FreeThrow(a) => a.fmt(f),
+            EventAction::Turnover(a) => a.fmt(f),
+            EventAction::Foul(a) => a.fmt(f),
+            EventAction::Violation(a) => a.fmt(f),
+            EventAction::Timeout(a) => a.fmt(f),
+            EventAction::Other(code) => write!(f, "{}", code),
+        }
+    }
+}
+
+impl Serialize for EventAction {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
@@ -0,0 +1,373 @@
+use crate::nba::error::NbaError;
+use crate::nba::event_codes::{EventAction, EventMsgType};
+
+use std::{convert::TryFrom, fmt, str::FromStr};
+use serde::{Deserialize, Serialize, Serializer};
+
+/// Time left in the period, as shown on the scoreboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GameClock {
+    tenths: u32,
+}
+
+impl GameClock {
+    pub fn seconds_remaining(&self) -> f64 {
+        self.tenths as f64 / 10.0
+    }
+}
+
+impl FromStr for GameClock {
+    type Err = NbaError;
+    /// Accepts `11:42`, `0:05.3` and `5.3`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || NbaError::SchemaMismatch(format!("expected a game clock like 11:42, got {:?}", s));
+        let (minutes, seconds) = match s.trim().split_once(':') {
+            Some((m, sec)) => (m.parse::<u32>().map_err(|_| invalid())?, sec),
+            None => (0, s.trim()),
+        };
+        let (whole, tenth) = match seconds.split_once('.') {
+            Some((w, t)) => (w, t.get(..1).unwrap_or("0")),
+            None => (seconds, "0"),
+        };
+        let whole: u32 = whole.parse().map_err(|_| invalid())?;
+        let tenth: u32 = tenth.parse().map_err(|_| invalid())?;
+        if whole >= 60 {
+            return Err(invalid());
+        }
+        Ok(GameClock { tenths: (minutes * 60 + whole) * 10 + tenth })
+    }
+}
+
+impl fmt::Display for GameClock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let seconds = self.tenths / 10;
+        match self.tenths % 10 {
+            0 => write!(f, "{}:{:02}", seconds / 60, seconds % 60),
+            tenth => write!(f, "{}:{:02}.{}", seconds / 60, seconds % 60, tenth),
+        }
+    }
+}
+
+impl Serialize for GameClock {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Who a player slot of an event refers to, from the `PERSONnTYPE` column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PersonRole {
+    HomePlayer,
+    VisitorPlayer,
+    /// Team events, e.g. a team rebound or a coach's technical
+    HomeTeam,
+    VisitorTeam,
+    Other(i64),
+}
+
+impl From<i64> for PersonRole {
+    fn from(person_type: i64) -> Self {
+        match person_type {
+            4 => PersonRole::HomePlayer,
+            5 => PersonRole::VisitorPlayer,
+            2 | 6 => PersonRole::HomeTeam,
+            3 | 7 => PersonRole::VisitorTeam,
+            other => PersonRole::Other(other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EventPlayer {
+    /// Player id, or the team id when `role` is a team
+    pub id: i64,
+    pub name: Option<String>,
+    pub team_id: Option<i64>,
+    pub team_abbreviation: Option<String>,
+    pub role: PersonRole,
+}
+
+/// Running score after an event, only set on events that change it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Score {
+    pub visitor: u32,
+    pub home: u32,
+}
+
+impl FromStr for Score {
+    type Err = NbaError;
+    /// stats.nba.com writes the score as `visitor - home`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || NbaError::SchemaMismatch(format!("expected a score like 98 - 102, got {:?}", s));
+        let (visitor, home) = s.split_once('-').ok_or_else(invalid)?;
+        Ok(Score {
+            visitor: visitor.trim().parse().map_err(|_| invalid())?,
+            home: home.trim().parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// One row of the PlayByPlay result set of `playbyplayv2`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "PlayByPlayRow")]
+pub struct PlayByPlayEvent {
+    pub game_id: String,
+    pub event_num: i64,
+    pub event_type: EventMsgType,
+    pub action: EventAction,
+    pub period: i64,
+    pub clock: GameClock,
+    pub home_description: Option<String>,
+    pub neutral_description: Option<String>,
+    pub visitor_description: Option<String>,
+    pub score: Option<Score>,
+    /// Home minus visitor points, only set on events that change the score
+    pub margin: Option<i64>,
+    /// Points the shot is worth when made, 2 or 3, only set on field goals
+    pub shot_value: Option<u8>,
+    /// Up to three players in the order nba.com lists them, the first is
+    /// usually the one who made the play
+    pub players: [Option<EventPlayer>; 3],
+    pub video_available: bool,
+}
+
+/// What `-k` filters on. Besides event types and named actions some kinds
+/// are about a specific player slot, e.g. the blocker of a missed shot is
+/// its third player.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    Type(EventMsgType),
+    Action(EventAction),
+    /// Made or missed field goal
+    Shot,
+    ThreePointer,
+    Assist,
+    Block,
+    Steal,
+    FoulDrawn,
+}
+
+const NAMED_KINDS: &[(&str, EventKind)] = &[
+    ("shot", EventKind::Shot),
+    ("3pt", EventKind::ThreePointer),
+    ("three pointer", EventKind::ThreePointer),
+    ("assist", EventKind::Assist),
+    ("block", EventKind::Block),
+    ("steal", EventKind::Steal),
+    ("foul drawn", EventKind::FoulDrawn),
+];
+
+// How playbyplayv2 descriptions mark a three point attempt, as a word of its
+// own. `sql_condition` looks for it the same way, case and all.
+const THREE_POINT_WORD: &str = "3PT";
+
+impl EventKind {
+    pub fn matches(&self, event: &PlayByPlayEvent) -> bool {
+        let is_shot = matches!(event.event_type, EventMsgType::MadeShot | EventMsgType::MissedShot);
+        match self {
+            EventKind::Type(event_type) => event.event_type == *event_type,
+            EventKind::Action(action) => event.action == *action,
+            EventKind::Shot => is_shot,
+            EventKind::ThreePointer => is_shot && event.shot_value == Some(3),
+            EventKind::Assist => event.event_type == EventMsgType::MadeShot && event.players[1].is_some(),
+            EventKind::Block => event.event_type == EventMsgType::MissedShot && event.players[2].is_some(),
+            EventKind::Steal => event.event_type == EventMsgType::Turnover && event.players[1].is_some(),
+            EventKind::FoulDrawn => event.event_type == EventMsgType::Foul && event.players[1].is_some(),
+        }
+    }
+
+    /// Player slots (0 based) that count as "the player" of this kind, a
+    /// block belongs to the blocker not the shooter.
+    pub fn player_slots(&self) -> &'static [usize] {
+        match self {
+            EventKind::Assist | EventKind::Steal | EventKind::FoulDrawn => &[1],
+            EventKind::Block => &[2],
+            EventKind::Type(EventMsgType::Substitution) => &[0, 1],
+            EventKind::Type(EventMsgType::JumpBall) => &[0, 1, 2],
+            _ => &[0],
+        }
+    }
+
+    /// The same test as `matches` against a row of the play by play table.
+    pub fn sql_condition(&self) -> String {
+        let shot_types = format!("eventmsgtype IN ({}, {})", EventMsgType::MadeShot.code(), EventMsgType::MissedShot.code());
+        match self {
+            EventKind::Type(event_type) => format!("eventmsgtype = {}", event_type.code()),
+            EventKind::Action(action) => {
+                let type_codes: Vec<String> = action.event_types().iter().map(|t| t.code().to_string()).collect();
+                format!("eventmsgtype IN ({}) AND eventmsgactiontype = {}", type_codes.join(", "), action.code())
+            }
+            EventKind::Shot => shot_types,
+            EventKind::ThreePointer => format!(
+                "{} AND ((' ' || COALESCE(homedescription, '') || ' ') GLOB '* {word} *' OR (' ' || COALESCE(visitordescription, '') || ' ') GLOB '* {word} *')",
+                shot_types, word = THREE_POINT_WORD),
+            EventKind::Assist => format!("eventmsgtype = {} AND COALESCE(player2_id, 0) != 0", EventMsgType::MadeShot.code()),
+            EventKind::Block => format!("eventmsgtype = {} AND COALESCE(player3_id, 0) != 0", EventMsgType::MissedShot.code()),
+            EventKind::Steal => format!("eventmsgtype = {} AND COALESCE(player2_id, 0) != 0", EventMsgType::Turnover.code()),
+            EventKind::FoulDrawn => format!("eventmsgtype = {} AND COALESCE(player2_id, 0) != 0", EventMsgType::Foul.code()),
+        }
+    }
+}
+
+impl FromStr for EventKind {
+    type Err = NbaError;
+    /// Accepts a kind, an event type or an action name, case, `-` and `_`
+    /// insensitive: `block`, `missed-shot`, `running pull-up jump shot`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let wanted = normalize_kind(s);
+        let named_kind = NAMED_KINDS.iter()
+            .find(|(name, _)| normalize_kind(name) == wanted)
+            .map(|(_, kind)| *kind);
+        let event_type = || EventMsgType::ALL.iter()
+            .find(|t| normalize_kind(&t.to_string()) == wanted)
+            .map(|&t| EventKind::Type(t));
+        let action = || EventAction::all()
+            .find(|a| normalize_kind(&a.to_string()) == wanted)
+            .map(EventKind::Action);
+        named_kind.or_else(event_type).or_else(action).ok_or_else(|| {
+            let mut kinds: Vec<String> = NAMED_KINDS.iter().map(|(name, _)| name.to_string()).collect();
+            kinds.extend(EventMsgType::ALL.iter().map(|t| t.to_string()));
+            NbaError::InvalidParam(format!("unknown event kind {:?}, expected a shot type, turnover or foul name or one of: {}", s, kinds.join(", ")))
+        })
+    }
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EventKind::Type(event_type) => event_type.fmt(f),
+            EventKind::Action(action) => action.fmt(f),
+            named => {
+                let name = NAMED_KINDS.iter().find(|(_, kind)| kind == named).map_or("", |(name, _)| name);
+                write!(f, "{}", name)
+            }
+        }
+    }
+}
+
+fn normalize_kind(kind: &str) -> String {
+    kind.to_lowercase()
+        .split(|c: char| c.is_whitespace() || c == '-' || c == '_')
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Raw layout of a row, turned into a `PlayByPlayEvent` once deserialized
+#[derive(Deserialize)]
+struct PlayByPlayRow {
+    #[serde(rename = "GAME_ID")]
+    game_id: String,
+    #[serde(rename = "EVENTNUM")]
+    event_num: i64,
+    #[serde(rename = "EVENTMSGTYPE")]
+    event_msg_type: i64,
+    #[serde(rename = "EVENTMSGACTIONTYPE")]
+    event_msg_action_type: i64,
+    #[serde(rename = "PERIOD")]
+    period: i64,
+    #[serde(rename = "PCTIMESTRING")]
+    clock: String,
+    #[serde(rename = "HOMEDESCRIPTION")]
+    home_description: Option<String>,
+    #[serde(rename = "NEUTRALDESCRIPTION")]
+    neutral_description: Option<String>,
+    #[serde(rename = "VISITORDESCRIPTION")]
+    visitor_description: Option<String>,
+    #[serde(rename = "SCORE")]
+    score: Option<String>,
+    #[serde(rename = "SCOREMARGIN")]
+    score_margin: Option<String>,
+    #[serde(rename = "PERSON1TYPE")]
+    person1_type: Option<i64>,
+    #[serde(rename = "PLAYER1_ID")]
+    player1_id: Option<i64>,
+    #[serde(rename = "PLAYER1_NAME")]
+    player1_name: Option<String>,
+    #[serde(rename = "PLAYER1_TEAM_ID")]
+    player1_team_id: Option<i64>,
+    #[serde(rename = "PLAYER1_TEAM_ABBREVIATION")]
+    player1_team_abbreviation: Option<String>,
+    #[serde(rename = "PERSON2TYPE")]
+    person2_type: Option<i64>,
+    #[serde(rename = "PLAYER2_ID")]
+    player2_id: Option<i64>,
+    #[serde(rename = "PLAYER2_NAME")]
+    player2_name: Option<String>,
+    #[serde(rename = "PLAYER2_TEAM_ID")]
+    player2_team_id: Option<i64>,
+    #[serde(rename = "PLAYER2_TEAM_ABBREVIATION")]
+    player2_team_abbreviation: Option<String>,
+    #[serde(rename = "PERSON3TYPE")]
+    person3_type: Option<i64>,
+    #[serde(rename = "PLAYER3_ID")]
+    player3_id: Option<i64>,
+    #[serde(rename = "PLAYER3_NAME")]
+    player3_name: Option<String>,
+    #[serde(rename = "PLAYER3_TEAM_ID")]
+    player3_team_id: Option<i64>,
+    #[serde(rename = "PLAYER3_TEAM_ABBREVIATION")]
+    player3_team_abbreviation: Option<String>,
+    #[serde(rename = "VIDEO_AVAILABLE_FLAG")]
+    video_available: Option<i64>,
+}
+
+impl TryFrom<PlayByPlayRow> for PlayByPlayEvent {
+    type Error = NbaError;
+    fn try_from(row: PlayByPlayRow) -> Result<Self, Self::Error> {
+        let margin = match row.score_margin.as_deref() {
+            None | Some("") => None,
+            Some("TIE") => Some(0),
+            Some(m) => Some(m.trim_start_matches('+').parse::<i64>()
+                .map_err(|_| NbaError::SchemaMismatch(format!("expected a score margin, got {:?}", m)))?),
+        };
+        let event_type = EventMsgType::from(row.event_msg_type);
+        // playbyplayv2 has no shot value column, the shot type is only
+        // written into the description, e.g. `Tatum 26' 3PT Jump Shot`
+        let shot_value = match event_type {
+            EventMsgType::MadeShot | EventMsgType::MissedShot => {
+                let is_three = [&row.home_description, &row.visitor_description].iter()
+                    .filter_map(|d| d.as_deref())
+                    .any(|d| d.split(' ').any(|word| word == THREE_POINT_WORD));
+                Some(if is_three { 3 } else { 2 })
+            }
+            _ => None,
+        };
+        Ok(PlayByPlayEvent {
+            game_id: row.game_id,
+            event_num: row.event_num,
+            event_type,
+            action: EventAction::decode(event_type, row.event_msg_action_type),
+            period: row.period,
+            clock: row.clock.parse()?,
+            home_description: row.home_description,
+            neutral_description: row.neutral_description,
+            visitor_description: row.visitor_description,
+            score: row.score.as_deref().filter(|s| !s.is_empty()).map(str::parse).transpose()?,
+            margin,
+            shot_value,
+            players: [
+                event_player(row.person1_type, row.player1_id, row.player1_name, row.player1_team_id, row.player1_team_abbreviation),
+                event_player(row.person2_type, row.player2_id, row.player2_name, row.player2_team_id, row.player2_team_abbreviation),
+                event_player(row.person3_type, row.player3_id, row.player3_name, row.player3_team_id, row.player3_team_abbreviation),
+            ],
+            video_available: row.video_available == Some(1),
+        })
+    }
+}
+
+// An empty slot has person type 0 (or null) and id 0
+fn event_player(person_type: Option<i64>, id: Option<i64>, name: Option<String>,
+    team_id: Option<i64>, team_abbreviation: Option<String>) -> Option<EventPlayer> {
+    match (person_type, id) {
+        (Some(person_type), Some(id)) if person_type != 0 && id != 0 => Some(EventPlayer {
+            id,
+            name,
+            team_id,
+            team_abbreviation,
+            role: PersonRole::from(person_type),
+        }),
+        _ => None,
+    }
+}
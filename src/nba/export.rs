@@ -0,0 +1,305 @@
+use crate::nba::db::{dataframe_from_rows, inferred_table_name, quote_identifier};
+use crate::nba::error::{NbaError, NbaResult};
+use crate::nba::filter::EventFilter;
+use crate::nba::output::OutputTable;
+use crate::nba::params::{NBAParam, Season};
+use crate::nba::warehouse::{table_columns, Warehouse, PLAYBYPLAY};
+
+use polars::prelude::{IpcWriter, ParquetWriter, SerWriter};
+use rusqlite::types::Value as SqlValue;
+use serde_json::Value;
+use std::{collections::{BTreeMap, HashMap}, fmt, fs::{self, File}, path::{Path, PathBuf}, str::FromStr};
+
+pub const DEFAULT_EXPORT_DIR: &str = "export";
+
+/// Partition of rows whose game date isn't stored anywhere.
+const UNKNOWN_PARTITION: &str = "unknown";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Parquet,
+    Ipc,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Parquet => "parquet",
+            ExportFormat::Ipc => "arrow",
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = NbaError;
+    fn from_str(s: &str) -> NbaResult<ExportFormat> {
+        match s.to_lowercase().as_str() {
+            "parquet" => Ok(ExportFormat::Parquet),
+            "ipc" | "arrow" => Ok(ExportFormat::Ipc),
+            _ => Err(NbaError::InvalidParam(format!("unknown export format {}, expected parquet or ipc", s))),
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportFormat::Parquet => f.write_str("parquet"),
+            ExportFormat::Ipc => f.write_str("ipc"),
+        }
+    }
+}
+
+/// Which rows of a warehouse table are exported. Every condition left
+/// empty keeps all rows.
+#[derive(Debug, Clone, Default)]
+pub struct ExportQuery {
+    pub table: String,
+    /// Only for the play by play table
+    pub filter: EventFilter,
+    /// e.g. 2021-22
+    pub season: Option<String>,
+    pub game_ids: Vec<String>,
+    pub player_ids: Vec<i64>,
+    /// Team ids or abbreviations
+    pub teams: Vec<String>,
+}
+
+impl ExportQuery {
+    // The WHERE clause over `table` aliased `e`, with its values in order
+    fn sql_condition(&self, columns: &[String]) -> NbaResult<(String, Vec<SqlValue>)> {
+        let has_column = |name: &str| columns.iter().any(|c| c.eq_ignore_ascii_case(name));
+        let require = |name: &str, option: &str| {
+            if has_column(name) {
+                Ok(())
+            } else {
+                Err(NbaError::InvalidParam(format!("{} can't be used on {}, it has no {} column", option, self.table, name)))
+            }
+        };
+        let is_playbyplay = self.table.eq_ignore_ascii_case(PLAYBYPLAY.name);
+        let mut conditions = Vec::new();
+        let mut values = Vec::new();
+        if let Some(season) = &self.season {
+            require("game_id", "--season")?;
+            Season::S(season.clone()).get_formatted_param()?;
+            // Digits 4 and 5 of a game id are the year the season starts in
+            conditions.push("substr(e.game_id, 4, 2) = ?".to_string());
+            values.push(SqlValue::Text(season[2..4].to_string()));
+        }
+        if !self.game_ids.is_empty() {
+            require("game_id", "--game")?;
+            conditions.push(format!("e.game_id IN ({})", placeholders(self.game_ids.len())));
+            values.extend(self.game_ids.iter().map(|id| SqlValue::Text(id.clone())));
+        }
+        let mut event_filter = self.filter.clone();
+        if !event_filter.is_empty() && !is_playbyplay {
+            return Err(NbaError::InvalidParam(format!("--filter only applies to {}, not {}", PLAYBYPLAY.name, self.table)));
+        }
+        if is_playbyplay {
+            // Same shorthands as playbyplay -p, about the player the event is about
+            for player_id in &self.player_ids {
+                event_filter = event_filter.and(format!("player={}", player_id).parse()?);
+            }
+            if !self.teams.is_empty() {
+                event_filter = event_filter.and(format!("team={}", self.teams.join(",")).parse()?);
+            }
+            let (filter_condition, filter_values) = event_filter.sql_condition(PLAYBYPLAY.name);
+            conditions.push(filter_condition);
+            values.extend(filter_values);
+        } else {
+            if !self.player_ids.is_empty() {
+                let player_column = ["player_id", "person_id"].iter()
+                    .find(|c| has_column(c))
+                    .ok_or_else(|| NbaError::InvalidParam(format!("--player can't be used on {}, it has no player_id column", self.table)))?;
+                conditions.push(format!("e.{} IN ({})", player_column, placeholders(self.player_ids.len())));
+                values.extend(self.player_ids.iter().map(|&id| SqlValue::Integer(id)));
+            }
+            if !self.teams.is_empty() {
+                require("team_id", "--team")?;
+                let (ids, abbreviations): (Vec<&String>, Vec<&String>) = self.teams.iter()
+                    .partition(|team| team.parse::<i64>().is_ok());
+                let mut team_conditions = Vec::new();
+                if !ids.is_empty() {
+                    team_conditions.push(format!("e.team_id IN ({})", placeholders(ids.len())));
+                    values.extend(ids.iter().filter_map(|id| id.parse().ok()).map(SqlValue::Integer));
+                }
+                if !abbreviations.is_empty() {
+                    require("team_abbreviation", "--team")?;
+                    team_conditions.push(format!("UPPER(e.team_abbreviation) IN ({})", placeholders(abbreviations.len())));
+                    values.extend(abbreviations.iter().map(|a| SqlValue::Text(a.to_uppercase())));
+                }
+                conditions.push(format!("({})", team_conditions.join(" OR ")));
+            }
+        }
+        if conditions.is_empty() {
+            return Ok(("1 = 1".to_string(), values));
+        }
+        Ok((conditions.join(" AND "), values))
+    }
+}
+
+/// Writes the rows of a warehouse table matching `query` under `dir`, one
+/// file per season and game date laid out the way DuckDB, pandas and spark
+/// read hive partitions:
+///
+/// `<dir>/<table>/season=2021-22/game_date=2022-06-02/part-0.parquet`
+///
+/// Game dates come from the table's own `game_date` or `game_date_est`, or from the
+/// stored league game log, `unknown` when neither has the game. Tables
+/// without a game id are written to `<dir>/<table>/part-0.<ext>`. Files of
+/// the partitions written are replaced, other partitions are left alone.
+/// Returns the files written with their row counts.
+pub fn export_table(warehouse: &Warehouse, query: &ExportQuery, format: ExportFormat, dir: &Path) -> NbaResult<OutputTable> {
+    let conn = warehouse.connection();
+    let table_name = query.table.to_lowercase();
+    let declared_columns: Vec<_> = table_columns(conn, &table_name)?.into_iter()
+        // The rowid alias means nothing outside the warehouse
+        .filter(|(name, _)| name != "id")
+        .collect();
+    if declared_columns.is_empty() {
+        return Err(NbaError::InvalidParam(format!("the warehouse has no table {}", query.table)));
+    }
+    let column_names: Vec<String> = declared_columns.iter().map(|(name, _)| name.clone()).collect();
+    let column_types: Vec<_> = declared_columns.iter().map(|(_, sql_type)| *sql_type).collect();
+    let game_id_col = column_names.iter().position(|c| c == "game_id");
+    let game_date_col = column_names.iter().position(|c| c == "game_date" || c == "game_date_est");
+
+    let (condition, values) = query.sql_condition(&column_names)?;
+    let select_columns: Vec<String> = column_names.iter().map(|c| format!("e.{}", quote_identifier(c))).collect();
+    // Games in order and their events in the order they happened, the row
+    // id only keeps the rest in a stable order
+    let mut order_by: Vec<String> = ["game_id", "eventnum"].iter()
+        .filter(|col| column_names.iter().any(|c| c == *col))
+        .map(|col| format!("e.{}", col))
+        .collect();
+    order_by.push("e.id".to_string());
+    let mut statement = conn.prepare(&format!(
+        "SELECT {} FROM {} AS e WHERE {} ORDER BY {}",
+        select_columns.join(", "), quote_identifier(&table_name), condition, order_by.join(", "),
+    ))?;
+    let rows = statement.query_map(rusqlite::params_from_iter(values), |row| {
+        (0..column_names.len())
+            .map(|col| row.get::<_, SqlValue>(col).map(|val| sql_to_json(&val)))
+            .collect::<rusqlite::Result<Vec<Value>>>()
+            .map(Value::Array)
+    })?.collect::<rusqlite::Result<Vec<Value>>>()?;
+
+    let game_dates = match (game_id_col, game_date_col) {
+        (Some(_), None) => league_game_dates(warehouse)?,
+        _ => HashMap::new(),
+    };
+    let mut partitions: BTreeMap<PathBuf, Vec<Value>> = BTreeMap::new();
+    for row in rows {
+        let partition = match game_id_col {
+            Some(col) => {
+                let game_id = row[col].as_str().unwrap_or_default();
+                let game_date = match game_date_col {
+                    Some(date_col) => row[date_col].as_str().map(|date| date.chars().take(10).collect()),
+                    None => game_dates.get(game_id).cloned(),
+                };
+                PathBuf::from(format!("season={}", season_of_game(game_id)))
+                    .join(format!("game_date={}", game_date.unwrap_or_else(|| UNKNOWN_PARTITION.to_string())))
+            }
+            None => PathBuf::new(),
+        };
+        partitions.entry(partition).or_default().push(row);
+    }
+
+    let mut written = OutputTable::new("Exported", &["file", "rows"]);
+    for (partition, partition_rows) in partitions {
+        let partition_dir = dir.join(&table_name).join(partition);
+        fs::create_dir_all(&partition_dir)?;
+        let path = partition_dir.join(format!("part-0.{}", format.extension()));
+        let df = dataframe_from_rows(&column_names, &column_types, &partition_rows)?;
+        match format {
+            ExportFormat::Parquet => ParquetWriter::new(File::create(&path)?).finish(&df)?,
+            ExportFormat::Ipc => IpcWriter::new(&mut File::create(&path)?).finish(&df)?,
+        };
+        written.rows.push(vec![path.display().to_string().into(), partition_rows.len().into()]);
+    }
+    Ok(written)
+}
+
+// Season a game id belongs to, 0042100401 was played in 2021-22
+fn season_of_game(game_id: &str) -> String {
+    match game_id.get(3..5).and_then(|yy| yy.parse::<u32>().ok()) {
+        Some(yy) => format!("20{:02}-{:02}", yy, (yy + 1) % 100),
+        None => UNKNOWN_PARTITION.to_string(),
+    }
+}
+
+// Game id to date of every game in the league game log, if one was stored
+fn league_game_dates(warehouse: &Warehouse) -> NbaResult<HashMap<String, String>> {
+    let log_table = inferred_table_name("leaguegamelog", "LeagueGameLog");
+    let log_columns = table_columns(warehouse.connection(), &log_table)?;
+    let has_column = |name: &str| log_columns.iter().any(|(c, _)| c == name);
+    if !has_column("game_id") || !has_column("game_date") {
+        return Ok(HashMap::new());
+    }
+    let mut statement = warehouse.connection().prepare(&format!(
+        "SELECT game_id, MIN(substr(game_date, 1, 10)) FROM {} WHERE game_date IS NOT NULL GROUP BY game_id",
+        quote_identifier(&log_table),
+    ))?;
+    let game_dates = statement.query_map([], |r| Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?)))?
+        .collect::<rusqlite::Result<HashMap<String, String>>>()?;
+    Ok(game_dates)
+}
+
+fn sql_to_json(val: &SqlValue) -> Value {
+    match val {
+        SqlValue::Null | SqlValue::Blob(_) => Value::Null,
+        SqlValue::Integer(n) => (*n).into(),
+        SqlValue::Real(n) => (*n).into(),
+        SqlValue::Text(s) => Value::String(s.clone()),
+    }
+}
+
+fn placeholders(count: usize) -> String {
+    vec!["?"; count].join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polars::prelude::{ParquetReader, SerReader};
+    use rusqlite::params;
+    use std::process;
+
+    // Two finals games with a date in the league game log and one without
+    fn warehouse() -> Warehouse {
+        let warehouse = Warehouse::open(":memory:", None).unwrap();
+        let conn = warehouse.connection();
+        for (game_id, eventnum) in [("0042100401", 2), ("0042100401", 1), ("0042100402", 1), ("0022100001", 1)] {
+            conn.execute("INSERT INTO playbyplay (game_id, eventnum, period) VALUES (?, ?, 1)", params![game_id, eventnum]).unwrap();
+        }
+        conn.execute("CREATE TABLE leaguegamelog_leaguegamelog (game_id TEXT, game_date TEXT)", []).unwrap();
+        for (game_id, game_date) in [("0042100401", "2022-06-02"), ("0042100402", "2022-06-05")] {
+            conn.execute("INSERT INTO leaguegamelog_leaguegamelog VALUES (?, ?)", params![game_id, game_date]).unwrap();
+        }
+        warehouse
+    }
+
+    #[test]
+    fn partitions_play_by_play_by_season_and_game_date() {
+        let dir = std::env::temp_dir().join(format!("nbasiren-export-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let query = ExportQuery { table: "playbyplay".to_string(), ..Default::default() };
+        let written = export_table(&warehouse(), &query, ExportFormat::Parquet, &dir).unwrap();
+
+        let files: Vec<(String, Value)> = written.rows.iter()
+            .map(|row| (row[0].as_str().unwrap().to_string(), row[1].clone()))
+            .collect();
+        let partition = |date: &str| dir.join("playbyplay/season=2021-22").join(format!("game_date={}", date)).join("part-0.parquet");
+        assert_eq!(files, [
+            (partition("2022-06-02").display().to_string(), Value::from(2)),
+            (partition("2022-06-05").display().to_string(), Value::from(1)),
+            (partition("unknown").display().to_string(), Value::from(1)),
+        ]);
+
+        let df = ParquetReader::new(File::open(partition("2022-06-02")).unwrap()).finish().unwrap();
+        assert_eq!(df.height(), 2);
+        let eventnums: Vec<Option<i64>> = df.column("eventnum").unwrap().i64().unwrap().into_iter().collect();
+        assert_eq!(eventnums, [Some(1), Some(2)]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,589 @@
+use crate::nba::db::quote_identifier;
+use crate::nba::error::{NbaError, NbaResult};
+use crate::nba::events::{EventKind, EventPlayer, GameClock, PersonRole, PlayByPlayEvent};
+
+use std::{collections::HashSet, fmt, str::FromStr};
+use rusqlite::types::Value as SqlValue;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Op {
+    pub(crate) fn compare<T: PartialOrd>(&self, left: T, right: T) -> bool {
+        match self {
+            Op::Eq => left == right,
+            Op::Ne => left != right,
+            Op::Lt => left < right,
+            Op::Le => left <= right,
+            Op::Gt => left > right,
+            Op::Ge => left >= right,
+        }
+    }
+
+    fn sql(&self) -> &'static str {
+        match self {
+            Op::Eq => "=",
+            Op::Ne => "!=",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+        }
+    }
+}
+
+// Longest first so `<=` isn't read as `<`
+const OPERATORS: &[(&str, Op)] = &[
+    ("<=", Op::Le),
+    (">=", Op::Ge),
+    ("!=", Op::Ne),
+    ("=", Op::Eq),
+    ("<", Op::Lt),
+    (">", Op::Gt),
+];
+
+#[derive(Debug, Clone)]
+enum Condition {
+    Event { kinds: Vec<EventKind>, negate: bool },
+    Player { ids: Vec<i64>, negate: bool },
+    Team { abbreviations: Vec<String>, ids: Vec<i64>, negate: bool },
+    Period(Op, i64),
+    Clock(Op, GameClock),
+    /// Absolute score difference when the event happened
+    Margin(Op, i64),
+}
+
+/// A parsed `--filter` expression, `and` separated conditions that must all
+/// hold:
+///
+/// `event=block and team=BOS and period>=4 and clock<=2:00 and margin<=5`
+///
+/// `event`, `player` and `team` take `=` or `!=` and a comma separated list,
+/// `period`, `clock` and `margin` take any comparison. `player` and `team`
+/// look at the player the event kind is about, the blocker of a block, the
+/// first player otherwise. `margin` is the score difference at the time of
+/// the event whichever team leads.
+#[derive(Debug, Clone, Default)]
+pub struct EventFilter {
+    source: String,
+    conditions: Vec<Condition>,
+}
+
+// State carried along while walking the events of a game in order
+struct FilterContext {
+    /// Team ids of each condition, empty for conditions not about teams
+    team_ids: Vec<HashSet<i64>>,
+    margin: i64,
+}
+
+impl EventFilter {
+    pub fn is_empty(&self) -> bool {
+        self.conditions.is_empty()
+    }
+
+    /// Adds the conditions of `other`, both have to hold.
+    pub fn and(mut self, other: EventFilter) -> EventFilter {
+        self.source = match (self.source.is_empty(), other.source.is_empty()) {
+            (_, true) => self.source,
+            (true, false) => other.source,
+            (false, false) => format!("{} and {}", self.source, other.source),
+        };
+        self.conditions.extend(other.conditions);
+        self
+    }
+
+    /// Smallest and largest period the filter can match, used to only ask
+    /// stats.nba.com for those periods. A margin depends on every score
+    /// before the event, so with a margin condition the game is asked for
+    /// from its start.
+    pub fn period_bounds(&self) -> (Option<i64>, Option<i64>) {
+        let mut start = None;
+        let mut end = None;
+        for condition in &self.conditions {
+            if let Condition::Period(op, period) = condition {
+                let (lower, upper) = match op {
+                    Op::Eq => (Some(*period), Some(*period)),
+                    Op::Gt => (Some(period + 1), None),
+                    Op::Ge => (Some(*period), None),
+                    Op::Lt => (None, Some(period - 1)),
+                    Op::Le => (None, Some(*period)),
+                    Op::Ne => (None, None),
+                };
+                start = start.max(lower);
+                end = match (end, upper) {
+                    (Some(e), Some(u)) => Some(u.min(e)),
+                    (e, u) => e.or(u),
+                };
+            }
+        }
+        if self.conditions.iter().any(|condition| matches!(condition, Condition::Margin(..))) {
+            start = None;
+        }
+        (start, end)
+    }
+
+    /// Events of a game, in game order, that match every condition.
+    pub fn apply<'a>(&self, events: &'a [PlayByPlayEvent]) -> Vec<&'a PlayByPlayEvent> {
+        let mut context = FilterContext {
+            team_ids: self.team_ids(events),
+            margin: 0,
+        };
+        let mut matching_events = Vec::new();
+        for event in events {
+            if let Some(margin) = event.margin {
+                context.margin = margin.abs();
+            }
+            if self.matches(event, &context) {
+                matching_events.push(event);
+            }
+        }
+        matching_events
+    }
+
+    fn matches(&self, event: &PlayByPlayEvent, context: &FilterContext) -> bool {
+        let subject = self.subject_slots(event)
+            .iter()
+            .filter_map(|&slot| event.players[slot].as_ref())
+            .collect::<Vec<&EventPlayer>>();
+        self.conditions.iter().zip(&context.team_ids).all(|(condition, team_ids)| match condition {
+            Condition::Event { kinds, negate } => kinds.iter().any(|k| k.matches(event)) != *negate,
+            Condition::Player { ids, negate } => subject.iter().any(|p| ids.contains(&p.id)) != *negate,
+            Condition::Team { negate, .. } => subject.iter()
+                .any(|p| team_of(p).is_some_and(|id| team_ids.contains(&id))) != *negate,
+            Condition::Period(op, period) => op.compare(event.period, *period),
+            Condition::Clock(op, clock) => op.compare(event.clock, *clock),
+            Condition::Margin(op, margin) => op.compare(context.margin, *margin),
+        })
+    }
+
+    // Slots of the first wanted kind the event is, the first player when
+    // the filter has no kind
+    fn subject_slots(&self, event: &PlayByPlayEvent) -> &'static [usize] {
+        self.wanted_kinds()
+            .find(|kind| kind.matches(event))
+            .map_or(&[0], |kind| kind.player_slots())
+    }
+
+    fn wanted_kinds(&self) -> impl Iterator<Item = &EventKind> {
+        self.conditions.iter()
+            .filter_map(|condition| match condition {
+                Condition::Event { kinds, negate: false } => Some(kinds),
+                _ => None,
+            })
+            .flatten()
+    }
+
+    // Team ids asked for, abbreviations are resolved through the players of
+    // the game since team events (a team rebound...) only carry the team id
+    fn team_ids(&self, events: &[PlayByPlayEvent]) -> Vec<HashSet<i64>> {
+        let mut all_team_ids = Vec::with_capacity(self.conditions.len());
+        for condition in &self.conditions {
+            let mut team_ids = HashSet::new();
+            if let Condition::Team { abbreviations, ids, .. } = condition {
+                team_ids.extend(ids);
+                let players = events.iter().flat_map(|event| event.players.iter().flatten());
+                for player in players {
+                    let is_wanted = player.team_abbreviation.as_ref()
+                        .is_some_and(|a| abbreviations.iter().any(|wanted| a.eq_ignore_ascii_case(wanted)));
+                    if let (true, Some(team_id)) = (is_wanted, player.team_id) {
+                        team_ids.insert(team_id);
+                    }
+                }
+            }
+            all_team_ids.push(team_ids);
+        }
+        all_team_ids
+    }
+
+    /// The same filter as a condition on the rows of the play by play table
+    /// `table_name`, which has to be aliased `e`, with the values to bind in
+    /// order. Team and margin conditions only look at rows of the same game.
+    /// `table_name` is quoted here.
+    pub fn sql_condition(&self, table_name: &str) -> (String, Vec<SqlValue>) {
+        let mut sql_conditions = Vec::new();
+        let mut values = Vec::new();
+        for condition in &self.conditions {
+            let sql_condition = match condition {
+                Condition::Event { kinds, negate } => {
+                    let any_kind = kinds.iter()
+                        .map(|kind| format!("({})", kind.sql_condition()))
+                        .collect::<Vec<_>>()
+                        .join(" OR ");
+                    negated(any_kind, *negate)
+                }
+                Condition::Player { ids, negate } => {
+                    let subject_condition = self.sql_subject_condition(|slot| {
+                        format!("e.player{}_id IN ({})", slot, placeholders(ids.len()))
+                    }, &mut values, |values| {
+                        values.extend(ids.iter().map(|&id| SqlValue::Integer(id)));
+                    });
+                    negated(subject_condition, *negate)
+                }
+                Condition::Team { abbreviations, ids, negate } => {
+                    // Same resolution as `team_ids`, abbreviation to id through the game's players
+                    let team_ids_query = format!(
+                        "SELECT CAST(t.player1_team_id AS INTEGER) FROM {} AS t WHERE t.game_id = e.game_id AND UPPER(t.player1_team_abbreviation) IN ({})",
+                        quote_identifier(table_name), placeholders(abbreviations.len()));
+                    let subject_condition = self.sql_subject_condition(|slot| {
+                        let team_of = format!(
+                            "CAST(CASE WHEN e.person{slot}type IN (2, 3, 6, 7) THEN e.player{slot}_id ELSE e.player{slot}_team_id END AS INTEGER)",
+                            slot = slot);
+                        format!("({team_of} IN ({}) OR {team_of} IN ({}))", team_ids_query, placeholders(ids.len()), team_of = team_of)
+                    }, &mut values, |values| {
+                        values.extend(abbreviations.iter().map(|a| SqlValue::Text(a.to_uppercase())));
+                        values.extend(ids.iter().map(|&id| SqlValue::Integer(id)));
+                    });
+                    negated(subject_condition, *negate)
+                }
+                Condition::Period(op, period) => {
+                    values.push(SqlValue::Integer(*period));
+                    format!("e.period {} ?", op.sql())
+                }
+                Condition::Clock(op, clock) => {
+                    values.push(SqlValue::Real(clock.seconds_remaining()));
+                    format!("(CAST(substr(e.pctimestring, 1, instr(e.pctimestring, ':') - 1) AS INTEGER) * 60 \
+                        + CAST(substr(e.pctimestring, instr(e.pctimestring, ':') + 1) AS REAL)) {} ?", op.sql())
+                }
+                Condition::Margin(op, margin) => {
+                    values.push(SqlValue::Integer(*margin));
+                    format!("ABS(COALESCE((SELECT CASE WHEN UPPER(m.scoremargin) = 'TIE' THEN 0 ELSE CAST(m.scoremargin AS INTEGER) END \
+                        FROM {} AS m WHERE m.game_id = e.game_id AND m.eventnum <= e.eventnum AND COALESCE(m.scoremargin, '') != '' \
+                        ORDER BY m.eventnum DESC LIMIT 1), 0)) {} ?", quote_identifier(table_name), op.sql())
+                }
+            };
+            sql_conditions.push(format!("({})", sql_condition));
+        }
+        if sql_conditions.is_empty() {
+            return ("1 = 1".to_string(), values);
+        }
+        (sql_conditions.join(" AND "), values)
+    }
+
+    // `slot_condition` tested against the player slots the event's kind is
+    // about, `push_values` is called once per slot condition emitted
+    fn sql_subject_condition(&self, slot_condition: impl Fn(usize) -> String,
+        values: &mut Vec<SqlValue>, push_values: impl Fn(&mut Vec<SqlValue>)) -> String {
+        let mut by_kind = Vec::new();
+        for kind in self.wanted_kinds() {
+            let slots = kind.player_slots().iter()
+                .map(|&slot| {
+                    push_values(values);
+                    slot_condition(slot + 1)
+                })
+                .collect::<Vec<_>>()
+                .join(" OR ");
+            by_kind.push(format!("(({}) AND ({}))", kind.sql_condition(), slots));
+        }
+        if by_kind.is_empty() {
+            push_values(values);
+            return slot_condition(1);
+        }
+        by_kind.join(" OR ")
+    }
+}
+
+impl FromStr for EventFilter {
+    type Err = NbaError;
+    fn from_str(s: &str) -> NbaResult<Self> {
+        let mut conditions = Vec::new();
+        for clause in split_and(s) {
+            conditions.push(parse_condition(clause)?);
+        }
+        Ok(EventFilter {
+            source: s.trim().to_string(),
+            conditions,
+        })
+    }
+}
+
+impl fmt::Display for EventFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+/// The `and` separated clauses of a condition list.
+pub(crate) fn split_and(s: &str) -> Vec<&str> {
+    let mut clauses = Vec::new();
+    let mut rest = s.trim();
+    while !rest.is_empty() {
+        // Matched on the bytes of `rest` itself, an ASCII match can't split a character
+        let separator = rest.as_bytes().windows(5).position(|window| window.eq_ignore_ascii_case(b" and "));
+        match separator {
+            Some(pos) => {
+                clauses.push(rest[..pos].trim());
+                rest = rest[pos + 5..].trim();
+            }
+            None => {
+                clauses.push(rest);
+                rest = "";
+            }
+        }
+    }
+    clauses
+}
+
+/// Splits `field<op>value` at its first operator, the field lowercased and
+/// the value unquoted.
+pub(crate) fn split_comparison(clause: &str) -> Option<(String, Op, &str)> {
+    let (pos, symbol, op) = OPERATORS.iter()
+        .filter_map(|(symbol, op)| clause.find(symbol).map(|pos| (pos, *symbol, *op)))
+        .min_by_key(|(pos, symbol, _)| (*pos, usize::MAX - symbol.len()))?;
+    let field = clause[..pos].trim().to_lowercase();
+    let value = clause[pos + symbol.len()..].trim().trim_matches(|c| c == '"' || c == '\'');
+    Some((field, op, value))
+}
+
+fn parse_condition(clause: &str) -> NbaResult<Condition> {
+    let invalid = |why: &str| NbaError::InvalidParam(format!("bad filter condition {:?}: {}", clause, why));
+    let (field, op, value) = split_comparison(clause).ok_or_else(|| invalid("expected field=value"))?;
+    if value.is_empty() {
+        return Err(invalid("missing value"));
+    }
+    let list = || value.split(',').map(str::trim).filter(|v| !v.is_empty());
+    let negate = match (field.as_str(), op) {
+        ("event" | "player" | "team", Op::Eq) => false,
+        ("event" | "player" | "team", Op::Ne) => true,
+        ("event" | "player" | "team", _) => return Err(invalid("only = and != work on this field")),
+        _ => false,
+    };
+    let number = || value.parse::<i64>().map_err(|_| invalid("expected a number"));
+    match field.as_str() {
+        "event" | "kind" => Ok(Condition::Event {
+            kinds: list().map(str::parse).collect::<NbaResult<_>>()?,
+            negate,
+        }),
+        "player" => Ok(Condition::Player {
+            ids: list().map(|id| id.parse::<i64>().map_err(|_| invalid("player ids are numbers"))).collect::<NbaResult<_>>()?,
+            negate,
+        }),
+        "team" => {
+            let (ids, abbreviations): (Vec<&str>, Vec<&str>) = list().partition(|t| t.parse::<i64>().is_ok());
+            Ok(Condition::Team {
+                abbreviations: abbreviations.iter().map(|a| a.to_uppercase()).collect(),
+                ids: ids.iter().filter_map(|id| id.parse().ok()).collect(),
+                negate,
+            })
+        }
+        "period" => Ok(Condition::Period(op, number()?)),
+        "clock" => Ok(Condition::Clock(op, value.parse().map_err(|_| invalid("expected a clock like 2:00"))?)),
+        "margin" => Ok(Condition::Margin(op, number()?.abs())),
+        _ => Err(invalid("fields are event, player, team, period, clock and margin")),
+    }
+}
+
+fn team_of(player: &EventPlayer) -> Option<i64> {
+    match player.role {
+        PersonRole::HomeTeam | PersonRole::VisitorTeam => Some(player.id),
+        _ => player.team_id,
+    }
+}
+
+fn negated(sql_condition: String, negate: bool) -> String {
+    if negate {
+        format!("NOT ({})", sql_condition)
+    } else {
+        sql_condition
+    }
+}
+
+fn placeholders(count: usize) -> String {
+    vec!["?"; count].join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nba::db::json_to_sql;
+    use crate::nba::warehouse::{Warehouse, PLAYBYPLAY};
+
+    use rusqlite::params_from_iter;
+    use serde_json::{json, Map, Value};
+
+    const CURRY: i64 = 201939;
+    const GREEN: i64 = 203110;
+    const TATUM: i64 = 1628369;
+    const HORFORD: i64 = 201143;
+
+    // A playbyplayv2 row of game 0042100401, GSW at home against BOS
+    fn row(eventnum: i64, msgtype: i64, period: i64, clock: &str, player: Option<(i64, &str)>, description: &str) -> Map<String, Value> {
+        let mut row: Map<String, Value> = PLAYBYPLAY.columns.iter()
+            .map(|(column, _)| (column.to_uppercase(), Value::Null))
+            .collect();
+        row.insert("GAME_ID".to_string(), json!("0042100401"));
+        row.insert("EVENTNUM".to_string(), json!(eventnum));
+        row.insert("EVENTMSGTYPE".to_string(), json!(msgtype));
+        row.insert("EVENTMSGACTIONTYPE".to_string(), json!(1));
+        row.insert("PERIOD".to_string(), json!(period));
+        row.insert("PCTIMESTRING".to_string(), json!(clock));
+        row.insert("VIDEO_AVAILABLE_FLAG".to_string(), json!(1));
+        let description_column = match player {
+            Some((_, "GSW")) => "HOMEDESCRIPTION",
+            Some(_) => "VISITORDESCRIPTION",
+            None => "NEUTRALDESCRIPTION",
+        };
+        row.insert(description_column.to_string(), json!(description));
+        for slot in 1..=3 {
+            row.insert(format!("PERSON{}TYPE", slot), json!(0));
+        }
+        if let Some((id, tricode)) = player {
+            with_player(&mut row, 1, id, tricode);
+        }
+        row
+    }
+
+    fn with_player(row: &mut Map<String, Value>, slot: usize, id: i64, tricode: &str) {
+        let (person_type, team_id) = if tricode == "GSW" { (4, 1610612744) } else { (5, 1610612738) };
+        row.insert(format!("PERSON{}TYPE", slot), json!(person_type));
+        row.insert(format!("PLAYER{}_ID", slot), json!(id));
+        row.insert(format!("PLAYER{}_NAME", slot), json!(format!("Player {}", id)));
+        row.insert(format!("PLAYER{}_TEAM_ID", slot), json!(team_id));
+        row.insert(format!("PLAYER{}_TEAM_ABBREVIATION", slot), json!(tricode));
+    }
+
+    fn scored(mut row: Map<String, Value>, score: &str, margin: &str) -> Map<String, Value> {
+        row.insert("SCORE".to_string(), json!(score));
+        row.insert("SCOREMARGIN".to_string(), json!(margin));
+        row
+    }
+
+    fn blocked(mut row: Map<String, Value>, id: i64, tricode: &str) -> Map<String, Value> {
+        with_player(&mut row, 3, id, tricode);
+        let blocker_column = if tricode == "GSW" { "HOMEDESCRIPTION" } else { "VISITORDESCRIPTION" };
+        row.insert(blocker_column.to_string(), json!(format!("Player {} BLOCK (1 BLK)", id)));
+        row
+    }
+
+    // Boston is up 17 going into the fourth, Golden State comes back
+    fn game() -> Vec<Map<String, Value>> {
+        vec![
+            row(1, 12, 1, "12:00", None, "Start of 1st Period"),
+            scored(row(2, 1, 1, "11:40", Some((CURRY, "GSW")), "Curry 26' 3PT Jump Shot (3 PTS)"), "0 - 3", "3"),
+            blocked(row(3, 2, 1, "11:20", Some((TATUM, "BOS")), "MISS Tatum 25' 3PT Jump Shot"), GREEN, "GSW"),
+            scored(row(4, 1, 3, "5:00", Some((TATUM, "BOS")), "Tatum 15' Jump Shot (20 PTS)"), "40 - 23", "-17"),
+            row(5, 5, 4, "11:30", Some((CURRY, "GSW")), "Curry Bad Pass Turnover (P1.T1)"),
+            scored(row(6, 1, 4, "2:00", Some((CURRY, "GSW")), "Curry 28' 3PT Pullup Jump Shot (30 PTS)"), "80 - 76", "-4"),
+            row(7, 5, 4, "1:30", Some((TATUM, "BOS")), "Tatum Lost Ball Turnover (P2.T3)"),
+            blocked(row(8, 2, 4, "0:40", Some((CURRY, "GSW")), "MISS Curry 3' Layup"), HORFORD, "BOS"),
+            scored(row(9, 1, 4, "0:20", Some((TATUM, "BOS")), "Tatum 3pt Jump Shot Tip (32 PTS)"), "82 - 76", "-6"),
+        ]
+    }
+
+    fn events(rows: &[Map<String, Value>]) -> Vec<PlayByPlayEvent> {
+        rows.iter()
+            .map(|row| serde_json::from_value(Value::Object(row.clone())).unwrap())
+            .collect()
+    }
+
+    fn warehouse(rows: &[Map<String, Value>]) -> Warehouse {
+        let warehouse = Warehouse::open(":memory:", None).unwrap();
+        for row in rows {
+            let columns: Vec<String> = row.keys().map(|column| column.to_lowercase()).collect();
+            warehouse.connection().execute(
+                &format!("INSERT INTO playbyplay ({}) VALUES ({})", columns.join(", "), placeholders(columns.len())),
+                params_from_iter(row.values().map(json_to_sql)),
+            ).unwrap();
+        }
+        warehouse
+    }
+
+    fn sql_matches(filter: &EventFilter, warehouse: &Warehouse) -> Vec<i64> {
+        let (condition, values) = filter.sql_condition(PLAYBYPLAY.name);
+        let mut statement = warehouse.connection()
+            .prepare(&format!("SELECT e.eventnum FROM playbyplay AS e WHERE {} ORDER BY e.eventnum", condition))
+            .unwrap();
+        let event_nums = statement.query_map(params_from_iter(values), |r| r.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<Vec<i64>>>()
+            .unwrap();
+        event_nums
+    }
+
+    // Event numbers the filter matches through `apply` and through
+    // `sql_condition`, which have to agree
+    fn matching(filter: &str) -> Vec<i64> {
+        let filter: EventFilter = filter.parse().unwrap();
+        let rows = game();
+        let applied: Vec<i64> = filter.apply(&events(&rows)).iter().map(|event| event.event_num).collect();
+        assert_eq!(applied, sql_matches(&filter, &warehouse(&rows)), "apply and sql differ on {}", filter);
+        applied
+    }
+
+    #[test]
+    fn parses_the_example_of_the_request() {
+        let filter: EventFilter = "event=block and team=BOS and period>=4 and clock<=2:00 and margin<=5".parse().unwrap();
+        assert_eq!(filter.conditions.len(), 5);
+        assert_eq!(filter.to_string(), "event=block and team=BOS and period>=4 and clock<=2:00 and margin<=5");
+        // Horford's block of Curry, the blocker is who the team is about
+        assert_eq!(matching("event=block and team=BOS and period>=4 and clock<=2:00 and margin<=5"), vec![8]);
+        assert_eq!(matching("event=block and team=GSW"), vec![3]);
+    }
+
+    #[test]
+    fn splits_clauses_on_and_in_any_case() {
+        assert_eq!(split_and(" event=turnover AND team=BOS and  period=4 "), vec!["event=turnover", "team=BOS", "period=4"]);
+        assert_eq!(split_and("team=BOS"), vec!["team=BOS"]);
+        assert!(split_and("  ").is_empty());
+        // Lowercasing these changes their length in bytes
+        assert_eq!(split_and("team=İST and period=4"), vec!["team=İST", "period=4"]);
+        assert_eq!(split_and("team=ẞẞẞ AND period=4 and clock<1:00"), vec!["team=ẞẞẞ", "period=4", "clock<1:00"]);
+    }
+
+    #[test]
+    fn rejects_bad_conditions() {
+        for filter in ["event>block", "period=fourth", "clock<=2 minutes", "quarter=4", "team=", "player=curry"] {
+            assert!(filter.parse::<EventFilter>().is_err(), "{} parsed", filter);
+        }
+    }
+
+    #[test]
+    fn rust_and_sql_agree() {
+        assert_eq!(matching("event=turnover"), vec![5, 7]);
+        assert_eq!(matching("event!=turnover and period=4"), vec![6, 8, 9]);
+        assert_eq!(matching("player=201939"), vec![2, 5, 6, 8]);
+        assert_eq!(matching("player!=201939 and period>=3"), vec![4, 7, 9]);
+        assert_eq!(matching("team=1610612744 and event=made-shot"), vec![2, 6]);
+        assert_eq!(matching("event=turnover and team=bos"), vec![7]);
+        assert_eq!(matching("clock<=0:40"), vec![8, 9]);
+        assert_eq!(matching("clock>11:30 and period=1"), vec![1, 2]);
+        assert_eq!(matching("margin>=10"), vec![4, 5]);
+        assert_eq!(matching("margin=0"), vec![1]);
+        assert_eq!(matching("event=block,steal and player=203110"), vec![3]);
+        assert_eq!(matching(""), vec![1, 2, 3, 4, 5, 6, 7, 8, 9]);
+    }
+
+    #[test]
+    fn period_bounds_narrow_to_every_period_condition() {
+        let bounds = |filter: &str| filter.parse::<EventFilter>().unwrap().period_bounds();
+        assert_eq!(bounds("event=block"), (None, None));
+        assert_eq!(bounds("period=2"), (Some(2), Some(2)));
+        assert_eq!(bounds("period>2 and period<5"), (Some(3), Some(4)));
+        assert_eq!(bounds("period!=4"), (None, None));
+    }
+
+    #[test]
+    fn three_pointers_are_the_same_rows_in_rust_and_sql() {
+        // Event 9 writes `3pt` in lowercase, which isn't how the shot type is marked
+        assert_eq!(matching("event=3pt"), vec![2, 3, 6]);
+        assert_eq!(matching("event=3pt and team=GSW"), vec![2, 6]);
+    }
+
+    #[test]
+    fn margin_counts_scores_before_the_periods_asked_for() {
+        let filter: EventFilter = "period>=4 and margin<=5".parse().unwrap();
+        assert_eq!(filter.period_bounds(), (None, None));
+        assert_eq!(matching("period>=4 and margin<=5"), vec![6, 7, 8]);
+        // Without a margin only the periods matched are needed
+        let filter: EventFilter = "period>=4 and period<=5 and event=turnover".parse().unwrap();
+        assert_eq!(filter.period_bounds(), (Some(4), Some(5)));
+        let filter: EventFilter = "period<=3 and margin>10".parse().unwrap();
+        assert_eq!(filter.period_bounds(), (None, Some(3)));
+    }
+}
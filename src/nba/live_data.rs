@@ -1,64 +1,781 @@
 
-const NBA_SCOREBOARD_URL: &str = "https://cdn.nba.com/static/json/liveData/scoreboard/todaysScoreboard_00.json";
+const NBA_SCOREBOARD_PATH: &str = "liveData/scoreboard/todaysScoreboard_00.json";
 
-use std::fs;
-use tabled::{Tabled, Table};
-use anyhow::Result;
+use crate::nba::transport::Transport;
+use crate::nba::db::result_sets;
+use crate::nba::error::{missing, NbaError, NbaResult};
+use crate::nba::event_codes::{EventAction, EventMsgType};
+use crate::nba::events::{EventPlayer, GameClock, PersonRole, PlayByPlayEvent, Score};
+use crate::nba::output::OutputTable;
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use std::{collections::{HashMap, HashSet}, fmt, fs};
+use serde::Deserialize;
+use serde_json::Value;
 
+    /// Regulation periods, any after them are overtimes.
+    const REGULATION_PERIODS: usize = 4;
 
-    #[derive(Tabled)]
-    struct QuarterBreakdown {
-        q1: i64,
-        q2: i64,
-        q3: i64,
-        q4: i64,
-        total: i64,
+    /// A team's points in each period played, overtimes included.
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct LineScore {
+        pub periods: Vec<i64>,
+        pub total: i64,
+    }
+
+    /// Where a game stands, as shown in the scoreboard's status column.
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum GameStatus {
+        /// Start time in local time, or the feed's text when it has none
+        Pregame { start: Option<DateTime<Local>>, text: String },
+        Live { period: i64, clock: Option<GameClock> },
+        Halftime,
+        Final { overtimes: i64 },
+    }
+
+    impl fmt::Display for GameStatus {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                GameStatus::Pregame { start: Some(start), .. } => write!(f, "{}", start.format("%-I:%M %p")),
+                GameStatus::Pregame { start: None, text } => f.write_str(text),
+                GameStatus::Live { period, clock: Some(clock) } if clock.seconds_remaining() == 0.0 => write!(f, "End {}", period_label(*period)),
+                GameStatus::Live { period, clock: Some(clock) } => write!(f, "{} {}", period_label(*period), clock),
+                GameStatus::Live { period, clock: None } => f.write_str(&period_label(*period)),
+                GameStatus::Halftime => f.write_str("Half"),
+                GameStatus::Final { overtimes: 0 } => f.write_str("Final"),
+                GameStatus::Final { overtimes: 1 } => f.write_str("Final/OT"),
+                GameStatus::Final { overtimes } => write!(f, "Final/{}OT", overtimes),
+            }
+        }
+    }
+
+    impl GameStatus {
+        fn from_live(game: &Value) -> NbaResult<GameStatus> {
+            let text = str_field(game, "gameStatusText")?.trim();
+            let period = game["period"].as_i64().unwrap_or(0);
+            Ok(match i64_field(game, "gameStatus")? {
+                1 => GameStatus::Pregame {
+                    start: game["gameTimeUTC"].as_str()
+                        .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+                        .map(|time| time.with_timezone(&Local)),
+                    text: text.to_string(),
+                },
+                3 => GameStatus::Final { overtimes: (period - REGULATION_PERIODS as i64).max(0) },
+                _ if text.starts_with("Half") => GameStatus::Halftime,
+                _ => GameStatus::Live { period, clock: game["gameClock"].as_str().and_then(parse_duration_clock) },
+            })
+        }
     }
     
-    pub fn get_endpoint_metadata() -> Result<serde_json::Value> {
+    pub fn get_endpoint_metadata() -> NbaResult<serde_json::Value> {
         let data = fs::read_to_string("endpoints_v2.json")?;
-        let json: serde_json::Value =
-            serde_json::from_str(&data)?;
+        let json: serde_json::Value = serde_json::from_str(&data)
+            .map_err(|e| NbaError::SchemaMismatch(format!("endpoints_v2.json is not valid json: {}", e)))?;
         Ok(json)
     }
     
-    fn extract_team_name(team_data: &serde_json::Value) -> Result<String> {
-        let team_name = team_data.get("teamName").unwrap().as_str().unwrap();
-        let team_city = team_data.get("teamCity").unwrap().as_str().unwrap();
-        let team_tri_code = team_data.get("teamTricode").unwrap().as_str().unwrap();
+    fn str_field<'a>(data: &'a Value, key: &str) -> NbaResult<&'a str> {
+        data[key].as_str().ok_or_else(|| missing(key))
+    }
+
+    fn i64_field(data: &Value, key: &str) -> NbaResult<i64> {
+        data[key].as_i64().ok_or_else(|| missing(key))
+    }
+
+    fn extract_team_name(team_data: &serde_json::Value) -> NbaResult<String> {
+        let team_name = str_field(team_data, "teamName")?;
+        let team_city = str_field(team_data, "teamCity")?;
+        let team_tri_code = str_field(team_data, "teamTricode")?;
         Ok(format!("({}) {} {}", team_tri_code, team_city, team_name))
     }
     
-    fn extract_quarter_info(team_data: &serde_json::Value) -> Result<QuarterBreakdown> {
-        let final_score = team_data.get("score").unwrap().as_i64().unwrap();
-        let game_periods = team_data.get("periods").unwrap().as_array().unwrap();
-        let mut quarter_data: Vec<i64> = vec![0; 4];
-        for (i ,period_info) in game_periods.iter().enumerate() {
-          quarter_data[i] = period_info.get("score").unwrap().as_i64().unwrap();
+    fn extract_line_score(team_data: &serde_json::Value) -> NbaResult<LineScore> {
+        let game_periods = team_data["periods"].as_array().ok_or_else(|| missing("periods"))?;
+        let periods = game_periods.iter()
+            .map(|period_info| i64_field(period_info, "score"))
+            .collect::<NbaResult<_>>()?;
+        Ok(LineScore { periods, total: i64_field(team_data, "score")? })
+    }
+
+    /// A team's line of the scoreboard, without scores before the game.
+    struct ScoreboardRow {
+        game_id: String,
+        side: &'static str,
+        team: String,
+        status: GameStatus,
+        line_score: Option<LineScore>,
+    }
+
+    /// One row per team of each game, with a column for every period
+    /// played. Overtime columns are only there if a game went to overtime,
+    /// and left empty for the games that didn't.
+    fn scoreboard_table(rows: Vec<ScoreboardRow>) -> OutputTable {
+        let period_count = rows.iter()
+            .filter_map(|row| row.line_score.as_ref().map(|line| line.periods.len()))
+            .fold(REGULATION_PERIODS, usize::max);
+        let period_columns: Vec<String> = (1..=period_count as i64).map(|period| period_label(period).to_lowercase()).collect();
+        let mut columns = vec!["game_id", "side", "team", "status"];
+        columns.extend(period_columns.iter().map(String::as_str));
+        columns.push("total");
+        let mut scoreboard_table = OutputTable::new("Scoreboard", &columns);
+        for row in rows {
+            let mut values: Vec<Value> = vec![row.game_id.into(), row.side.into(), row.team.into(), row.status.to_string().into()];
+            match row.line_score {
+                Some(line) => {
+                    values.extend((0..period_count).map(|i| line.periods.get(i).map_or(Value::Null, |&score| score.into())));
+                    values.push(line.total.into());
+                }
+                None => values.extend(vec![Value::Null; period_count + 1]),
+            }
+            scoreboard_table.rows.push(values);
         }
-        Ok(QuarterBreakdown{q1: quarter_data[0], q2: quarter_data[1], q3: quarter_data[2], q4: quarter_data[3], total: final_score})
+        scoreboard_table
     }
-    
-    pub fn fetch_scoreboard() -> Result<()> {
-        let r = ureq::get(NBA_SCOREBOARD_URL).call()?;
-        let json: serde_json::Value = r.into_json().unwrap();
-        let games = json.get("scoreboard").unwrap().get("games").unwrap().as_array().unwrap();
+
+    /// Today's games, one row per team with its quarter scores.
+    pub fn fetch_scoreboard(transport: &dyn Transport) -> NbaResult<OutputTable> {
+        live_scoreboard_from_json(&transport.get_cdn_json(NBA_SCOREBOARD_PATH)?)
+    }
+
+    /// The scoreboard of a live scoreboard response.
+    pub fn live_scoreboard_from_json(json: &Value) -> NbaResult<OutputTable> {
+        let games = json["scoreboard"]["games"].as_array().ok_or_else(|| missing("scoreboard.games"))?;
+        let mut rows = Vec::new();
         for g in games {
-            let mut scoreboard_table: Vec<QuarterBreakdown> = Vec::new();
-            let game_id = g.get("gameId").unwrap().as_str().unwrap();
-            let home = g.get("homeTeam").unwrap();
-            let away = g.get("awayTeam").unwrap();
-            let formatted_home_team_name = extract_team_name(home)?; //format!("({}) {} {}", teamTriCode, teamCity, teamName);
-            let formatted_away_team_name = extract_team_name(away)?;
-            let home_quarter_data = extract_quarter_info(home)?;
-            scoreboard_table.push(home_quarter_data);
-            let away_quarter_data = extract_quarter_info(away)?;
-            scoreboard_table.push(away_quarter_data);
-            let table = Table::new(scoreboard_table).to_string();
-            println!("game_id: {}", game_id);
-            println!("Home: {}\nAway: {}", formatted_home_team_name, formatted_away_team_name);
-            println!("{}", table);
-        }
-        
-        Ok(())
-    }
\ No newline at end of file
+            let game_id = str_field(g, "gameId")?;
+            let status = GameStatus::from_live(g)?;
+            for (side, team) in [("home", &g["homeTeam"]), ("away", &g["awayTeam"])] {
+                let line_score = match status {
+                    GameStatus::Pregame { .. } => None,
+                    _ => Some(extract_line_score(team)?),
+                };
+                rows.push(ScoreboardRow {
+                    game_id: game_id.to_string(),
+                    side,
+                    team: extract_team_name(team)?,
+                    status: status.clone(),
+                    line_score,
+                });
+            }
+        }
+        Ok(scoreboard_table(rows))
+    }
+
+    /// `Q1`..`Q4`, then `OT1`, `OT2`...
+    pub fn period_label(period: i64) -> String {
+        let regulation = REGULATION_PERIODS as i64;
+        if period > regulation { format!("OT{}", period - regulation) } else { format!("Q{}", period) }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct TeamScore {
+        pub tricode: String,
+        pub score: i64,
+    }
+
+    /// Where a game of the live scoreboard stands.
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct GameSnapshot {
+        pub game_id: String,
+        /// 1 scheduled, 2 live, 3 final
+        pub status: i64,
+        pub status_text: String,
+        pub period: i64,
+        /// Time left in the period, none before the game
+        pub clock: Option<GameClock>,
+        pub home: TeamScore,
+        pub away: TeamScore,
+    }
+
+    impl GameSnapshot {
+        /// Home score minus away score.
+        pub fn margin(&self) -> i64 {
+            self.home.score - self.away.score
+        }
+
+        /// e.g. `BOS 78 - 80 GSW`, away team first.
+        pub fn score_line(&self) -> String {
+            format!("{} {} - {} {}", self.away.tricode, self.away.score, self.home.score, self.home.tricode)
+        }
+    }
+
+    pub fn scoreboard_url(transport: &dyn Transport) -> String {
+        format!("{}/{}", transport.cdn_base_url(), NBA_SCOREBOARD_PATH)
+    }
+
+    /// Every game of a live scoreboard response.
+    pub fn scoreboard_snapshots(json: &Value) -> NbaResult<Vec<GameSnapshot>> {
+        let games = json["scoreboard"]["games"].as_array().ok_or_else(|| missing("scoreboard.games"))?;
+        let team_score = |team: &Value| -> NbaResult<TeamScore> {
+            Ok(TeamScore { tricode: str_field(team, "teamTricode")?.to_string(), score: i64_field(team, "score")? })
+        };
+        games.iter()
+            .map(|g| {
+                let home = team_score(&g["homeTeam"])?;
+                let away = team_score(&g["awayTeam"])?;
+                Ok(GameSnapshot {
+                    game_id: str_field(g, "gameId")?.to_string(),
+                    status: i64_field(g, "gameStatus")?,
+                    status_text: GameStatus::from_live(g)?.to_string(),
+                    period: g["period"].as_i64().unwrap_or(0),
+                    clock: g["gameClock"].as_str().and_then(parse_duration_clock),
+                    home,
+                    away,
+                })
+            })
+            .collect()
+    }
+
+    /// The live feeds' ISO 8601 clock, `PT02M41.00S`, as a game clock.
+    pub fn parse_duration_clock(clock: &str) -> Option<GameClock> {
+        let (minutes, seconds) = clock.strip_prefix("PT")?.strip_suffix('S')?.split_once('M')?;
+        format!("{}:{}", minutes.parse::<u32>().ok()?, seconds).parse().ok()
+    }
+
+    pub fn boxscore_url(transport: &dyn Transport, game_id: &str) -> String {
+        format!("{}/liveData/boxscore/boxscore_{}.json", transport.cdn_base_url(), game_id)
+    }
+
+    /// A game's live box score, see `boxscore_from_json`.
+    pub fn fetch_boxscore(transport: &dyn Transport, game_id: &str) -> NbaResult<OutputTable> {
+        boxscore_from_json(&transport.get_json(&boxscore_url(transport, game_id))?)
+    }
+
+    /// `1` scheduled, `2` live, `3` final, of a live box score response.
+    pub fn boxscore_game_status(json: &Value) -> NbaResult<i64> {
+        i64_field(&json["game"], "gameStatus")
+    }
+
+    /// The box score of a live box score response: a row per player who is
+    /// active, then a `Total` row, for each team, away team first. Starters
+    /// have a position, `on_court` tells who is on the floor right now.
+    pub fn boxscore_from_json(json: &Value) -> NbaResult<OutputTable> {
+        let game = &json["game"];
+        let game_id = str_field(game, "gameId")?;
+        let mut boxscore_table = OutputTable::new("Boxscore", &[
+            "game_id", "team", "player", "position", "on_court", "min", "pts", "reb", "ast", "fg", "3pt", "ft", "pf", "plus_minus",
+        ]);
+        for team in [&game["awayTeam"], &game["homeTeam"]] {
+            let tricode = str_field(team, "teamTricode")?;
+            let players = team["players"].as_array().ok_or_else(|| missing("players"))?;
+            // Inactive players are listed with a reason and no stats
+            for player in players.iter().filter(|player| player["status"].as_str() == Some("ACTIVE")) {
+                let mut row: Vec<Value> = vec![
+                    game_id.into(),
+                    tricode.into(),
+                    str_field(player, "name")?.into(),
+                    player["position"].as_str().map_or(Value::Null, Value::from),
+                    (player["oncourt"].as_str() == Some("1")).into(),
+                ];
+                row.extend(boxscore_stats(&player["statistics"])?);
+                boxscore_table.rows.push(row);
+            }
+            let mut total_row: Vec<Value> = vec![game_id.into(), tricode.into(), "Total".into(), Value::Null, Value::Null];
+            total_row.extend(boxscore_stats(&team["statistics"])?);
+            boxscore_table.rows.push(total_row);
+        }
+        Ok(boxscore_table)
+    }
+
+    pub fn playbyplay_url(transport: &dyn Transport, game_id: &str) -> String {
+        format!("{}/liveData/playbyplay/playbyplay_{}.json", transport.cdn_base_url(), game_id)
+    }
+
+    /// Whether the live play by play has the action ending the game.
+    pub fn live_game_ended(json: &Value) -> bool {
+        json["game"]["actions"].as_array().is_some_and(|actions| actions.iter()
+            .any(|action| action["actionType"].as_str() == Some("game") && action["subType"].as_str() == Some("end")))
+    }
+
+    /// The actions of a live play by play response as the events
+    /// `playbyplayv2` has, so the same filters work on them. Steals and
+    /// blocks are folded into the turnover or missed shot they come with,
+    /// like `playbyplayv2` does, instead of being events of their own.
+    pub fn live_events_from_json(json: &Value) -> NbaResult<Vec<PlayByPlayEvent>> {
+        let game = &json["game"];
+        let game_id = str_field(game, "gameId")?;
+        let actions = game["actions"].as_array().ok_or_else(|| missing("game.actions"))?;
+        // Actions only say whether their team is home or visitor, the other
+        // player of a steal, block or foul drawn is of the other team
+        let mut teams: HashMap<&str, (i64, &str)> = HashMap::new();
+        for action in actions {
+            if let (Some(location), Some(team_id), Some(tricode)) = (action["location"].as_str(), action["teamId"].as_i64(), action["teamTricode"].as_str()) {
+                teams.entry(location).or_insert((team_id, tricode));
+            }
+        }
+        let mut events = Vec::with_capacity(actions.len());
+        for action in actions {
+            let action_type = str_field(action, "actionType")?;
+            let event_type = match action_type {
+                "2pt" | "3pt" if action["shotResult"].as_str() == Some("Made") => EventMsgType::MadeShot,
+                "2pt" | "3pt" => EventMsgType::MissedShot,
+                "freethrow" => EventMsgType::FreeThrow,
+                "rebound" => EventMsgType::Rebound,
+                "turnover" => EventMsgType::Turnover,
+                "foul" => EventMsgType::Foul,
+                "violation" => EventMsgType::Violation,
+                "substitution" => EventMsgType::Substitution,
+                "timeout" => EventMsgType::Timeout,
+                "jumpball" => EventMsgType::JumpBall,
+                "ejection" => EventMsgType::Ejection,
+                "period" if action["subType"].as_str() == Some("start") => EventMsgType::PeriodStart,
+                "period" => EventMsgType::PeriodEnd,
+                "instantreplay" => EventMsgType::InstantReplay,
+                "stoppage" => EventMsgType::Stoppage,
+                // Kept only to tell when the game ended
+                "steal" | "block" | "game" => continue,
+                _ => EventMsgType::Unknown(0),
+            };
+            let location = action["location"].as_str().unwrap_or_default();
+            let other_location = if location == "h" { "v" } else { "h" };
+            let player = |id_key: &str, name_key: &str, location: &str| {
+                let &(team_id, tricode) = teams.get(location)?;
+                Some(EventPlayer {
+                    id: action[id_key].as_i64().filter(|&id| id != 0)?,
+                    name: action[name_key].as_str().map(str::to_string),
+                    team_id: Some(team_id),
+                    team_abbreviation: Some(tricode.to_string()),
+                    role: if location == "h" { PersonRole::HomePlayer } else { PersonRole::VisitorPlayer },
+                })
+            };
+            // Team rebounds, turnovers and timeouts have the team instead of a player
+            let first_player = player("personId", "playerNameI", location).or_else(|| {
+                let &(team_id, tricode) = teams.get(location)?;
+                action["teamId"].as_i64().filter(|&id| id != 0)?;
+                Some(EventPlayer {
+                    id: team_id,
+                    name: None,
+                    team_id: Some(team_id),
+                    team_abbreviation: Some(tricode.to_string()),
+                    role: if location == "h" { PersonRole::HomeTeam } else { PersonRole::VisitorTeam },
+                })
+            });
+            let players = match event_type {
+                EventMsgType::MadeShot => [first_player, player("assistPersonId", "assistPlayerNameInitial", location), None],
+                EventMsgType::MissedShot => [first_player, None, player("blockPersonId", "blockPlayerName", other_location)],
+                EventMsgType::Turnover => [first_player, player("stealPersonId", "stealPlayerName", other_location), None],
+                EventMsgType::Foul => [first_player, player("foulDrawnPersonId", "foulDrawnPlayerName", other_location), None],
+                // Which team the jumpers are on isn't given
+                EventMsgType::JumpBall => [
+                    jumper(action, "jumpBallWonPersonId", "jumpBallWonPlayerName"),
+                    jumper(action, "jumpBallLostPersonId", "jumpBallLostPlayerName"),
+                    first_player,
+                ],
+                _ => [first_player, None, None],
+            };
+            let description = action["description"].as_str().map(str::to_string);
+            let score = match (action["scoreAway"].as_str(), action["scoreHome"].as_str()) {
+                (Some(away), Some(home)) => format!("{} - {}", away, home).parse::<Score>().ok(),
+                _ => None,
+            };
+            let clock = str_field(action, "clock")?;
+            events.push(PlayByPlayEvent {
+                game_id: game_id.to_string(),
+                event_num: i64_field(action, "actionNumber")?,
+                event_type,
+                action: live_event_action(event_type, action),
+                period: i64_field(action, "period")?,
+                clock: parse_duration_clock(clock).ok_or_else(|| NbaError::SchemaMismatch(format!("expected a clock like PT11M58.00S, got {:?}", clock)))?,
+                home_description: description.clone().filter(|_| location == "h"),
+                neutral_description: description.clone().filter(|_| location != "h" && location != "v"),
+                visitor_description: description.filter(|_| location == "v"),
+                score,
+                margin: score.map(|score| score.home as i64 - score.visitor as i64),
+                shot_value: match action_type {
+                    "3pt" => Some(3),
+                    "2pt" => Some(2),
+                    _ => None,
+                },
+                players,
+                video_available: false,
+            });
+        }
+        Ok(events)
+    }
+
+    fn jumper(action: &Value, id_key: &str, name_key: &str) -> Option<EventPlayer> {
+        Some(EventPlayer {
+            id: action[id_key].as_i64().filter(|&id| id != 0)?,
+            name: action[name_key].as_str().map(str::to_string),
+            team_id: None,
+            team_abbreviation: None,
+            role: PersonRole::Other(0),
+        })
+    }
+
+    // The live feed names actions with a sub type and a descriptor, e.g.
+    // `Jump Shot` and `running pullup`, matched against the action names
+    fn live_event_action(event_type: EventMsgType, action: &Value) -> EventAction {
+        let squash = |name: &str| name.chars().filter(char::is_ascii_alphanumeric).collect::<String>().to_lowercase();
+        let type_name = squash(&event_type.to_string().replace("made ", "").replace("missed ", ""));
+        let sub_type = squash(action["subType"].as_str().unwrap_or_default());
+        let descriptor = squash(action["descriptor"].as_str().unwrap_or_default());
+        // The descriptor is more specific, `shooting` of a `personal` foul
+        let candidates = [
+            format!("{}{}", descriptor, sub_type),
+            format!("{}{}{}", descriptor, sub_type, type_name),
+            format!("{}{}", descriptor, type_name),
+            format!("{}{}", sub_type, descriptor),
+            format!("{}{}{}", sub_type, descriptor, type_name),
+            sub_type.clone(),
+            format!("{}{}", sub_type, type_name),
+            format!("{}{}", type_name, sub_type),
+        ];
+        let named_actions: Vec<EventAction> = EventAction::all().filter(|a| a.event_types().contains(&event_type)).collect();
+        candidates.iter()
+            .filter(|candidate| !candidate.is_empty())
+            .find_map(|candidate| named_actions.iter().find(|a| squash(&a.to_string()) == *candidate))
+            .copied()
+            .unwrap_or_else(|| EventAction::decode(event_type, 0))
+    }
+
+    /// Live play by play events, one row each.
+    pub fn live_events_table(events: &[&PlayByPlayEvent]) -> OutputTable {
+        let mut table = OutputTable::new("PlayByPlay", &[
+            "game_id", "event_num", "period", "clock", "event", "action", "team", "player", "description", "score",
+        ]);
+        for event in events {
+            let first_player = event.players[0].as_ref();
+            let description = [&event.home_description, &event.visitor_description, &event.neutral_description].iter()
+                .find_map(|d| (*d).clone());
+            table.rows.push(vec![
+                event.game_id.clone().into(),
+                event.event_num.into(),
+                period_label(event.period).into(),
+                event.clock.to_string().into(),
+                event.event_type.to_string().into(),
+                match event.action {
+                    EventAction::Other(_) => Value::Null,
+                    action => action.to_string().into(),
+                },
+                first_player.and_then(|p| p.team_abbreviation.clone()).map_or(Value::Null, Value::from),
+                first_player.and_then(|p| p.name.clone()).map_or(Value::Null, Value::from),
+                description.map_or(Value::Null, Value::from),
+                event.score.map_or(Value::Null, |score| format!("{} - {}", score.visitor, score.home).into()),
+            ]);
+        }
+        table
+    }
+
+    // min through plus_minus of a player's or a team's statistics
+    fn boxscore_stats(stats: &Value) -> NbaResult<Vec<Value>> {
+        let stat = |key: &str| -> NbaResult<i64> {
+            // plusMinusPoints comes as a float
+            stats[key].as_i64().or_else(|| stats[key].as_f64().map(|n| n as i64)).ok_or_else(|| missing(key))
+        };
+        let split = |made: &str, attempted: &str| -> NbaResult<Value> {
+            Ok(format!("{}-{}", stat(made)?, stat(attempted)?).into())
+        };
+        Ok(vec![
+            // Players who haven't checked in have an empty clock
+            stats["minutes"].as_str().and_then(parse_duration_clock).map_or(Value::Null, |minutes| minutes.to_string().into()),
+            stat("points")?.into(),
+            stat("reboundsTotal")?.into(),
+            stat("assists")?.into(),
+            split("fieldGoalsMade", "fieldGoalsAttempted")?,
+            split("threePointersMade", "threePointersAttempted")?,
+            split("freeThrowsMade", "freeThrowsAttempted")?,
+            stat("foulsPersonal")?.into(),
+            stat("plusMinusPoints")?.into(),
+        ])
+    }
+
+    #[derive(Deserialize)]
+    struct GameHeader {
+        #[serde(rename = "GAME_DATE_EST")]
+        game_date_est: String,
+        #[serde(rename = "GAME_ID")]
+        game_id: String,
+        #[serde(rename = "GAME_STATUS_ID")]
+        game_status_id: i64,
+        #[serde(rename = "GAME_STATUS_TEXT")]
+        game_status_text: String,
+        #[serde(rename = "LIVE_PERIOD")]
+        live_period: Option<i64>,
+        #[serde(rename = "LIVE_PC_TIME")]
+        live_pc_time: Option<String>,
+        #[serde(rename = "HOME_TEAM_ID")]
+        home_team_id: i64,
+    }
+
+    #[derive(Deserialize)]
+    struct LineScoreRow {
+        #[serde(rename = "GAME_ID")]
+        game_id: String,
+        #[serde(rename = "TEAM_ID")]
+        team_id: i64,
+        #[serde(rename = "TEAM_ABBREVIATION")]
+        team_abbreviation: String,
+        #[serde(rename = "TEAM_CITY_NAME")]
+        team_city_name: String,
+        #[serde(rename = "TEAM_NAME")]
+        team_name: String,
+        #[serde(rename = "PTS_QTR1")]
+        pts_qtr1: Option<i64>,
+        #[serde(rename = "PTS_QTR2")]
+        pts_qtr2: Option<i64>,
+        #[serde(rename = "PTS_QTR3")]
+        pts_qtr3: Option<i64>,
+        #[serde(rename = "PTS_QTR4")]
+        pts_qtr4: Option<i64>,
+        #[serde(rename = "PTS")]
+        pts: Option<i64>,
+        /// PTS_OT1 to PTS_OT10 among the rest
+        #[serde(flatten)]
+        other_columns: HashMap<String, Value>,
+    }
+
+    impl LineScoreRow {
+        // Overtimes beyond the ones played are 0, not null
+        fn line_score(&self, overtimes: i64) -> Option<LineScore> {
+            let mut periods: Vec<i64> = [self.pts_qtr1, self.pts_qtr2, self.pts_qtr3, self.pts_qtr4].iter()
+                .map(|score| score.unwrap_or(0))
+                .collect();
+            periods.extend((1..=overtimes).map(|ot| self.other_columns.get(&format!("PTS_OT{}", ot)).and_then(Value::as_i64).unwrap_or(0)));
+            Some(LineScore { periods, total: self.pts? })
+        }
+    }
+
+    impl GameHeader {
+        fn status(&self) -> GameStatus {
+            let text = self.game_status_text.trim();
+            let period = self.live_period.unwrap_or(0);
+            match self.game_status_id {
+                1 => GameStatus::Pregame {
+                    start: NaiveDate::parse_from_str(self.game_date_est.get(..10).unwrap_or_default(), "%Y-%m-%d").ok()
+                        .and_then(|date| eastern_start_time(date, text)),
+                    text: text.to_string(),
+                },
+                3 => GameStatus::Final { overtimes: (period - REGULATION_PERIODS as i64).max(0) },
+                _ if text.starts_with("Half") => GameStatus::Halftime,
+                _ => GameStatus::Live {
+                    period,
+                    clock: self.live_pc_time.as_deref().and_then(|clock| clock.trim().parse().ok()),
+                },
+            }
+        }
+    }
+
+    // scoreboardV2 has start times in US Eastern time, e.g. `8:30 pm ET`
+    fn eastern_start_time(game_date: NaiveDate, status_text: &str) -> Option<DateTime<Local>> {
+        let time = NaiveTime::parse_from_str(status_text.trim_end_matches("ET").trim(), "%I:%M %p").ok()?;
+        let utc_offset_hours = if us_daylight_saving(game_date) { 4 } else { 5 };
+        let start = NaiveDateTime::new(game_date, time) + Duration::hours(utc_offset_hours);
+        Some(Utc.from_utc_datetime(&start).with_timezone(&Local))
+    }
+
+    // From the second Sunday of March to the first Sunday of November
+    fn us_daylight_saving(date: NaiveDate) -> bool {
+        let nth_sunday = |month: u32, n: i64| {
+            let first = NaiveDate::from_ymd(date.year(), month, 1);
+            first + Duration::days((7 - first.weekday().num_days_from_sunday() as i64) % 7 + 7 * (n - 1))
+        };
+        date >= nth_sunday(3, 2) && date < nth_sunday(11, 1)
+    }
+
+    /// The scoreboard of a `scoreboardV2` response, laid out like the live one.
+    pub fn scoreboard_from_json(endpoint_json: &Value) -> NbaResult<OutputTable> {
+        let mut game_headers: Vec<GameHeader> = Vec::new();
+        let mut line_scores: Vec<LineScoreRow> = Vec::new();
+        for data_set in result_sets(endpoint_json)? {
+            match data_set.name {
+                "GameHeader" => game_headers = data_set.deserialize_rows()?,
+                "LineScore" => line_scores = data_set.deserialize_rows()?,
+                _ => {}
+            }
+        }
+        let mut rows = Vec::new();
+        // The game header lists a game once per broadcaster on some nights
+        let mut seen_games = HashSet::new();
+        for header in game_headers.iter().filter(|h| seen_games.insert(h.game_id.clone())) {
+            let status = header.status();
+            let overtimes = (header.live_period.unwrap_or(0) - REGULATION_PERIODS as i64).max(0);
+            let mut teams: Vec<&LineScoreRow> = line_scores.iter().filter(|l| l.game_id == header.game_id).collect();
+            teams.sort_by_key(|l| l.team_id != header.home_team_id);
+            for line in teams {
+                let side = if line.team_id == header.home_team_id { "home" } else { "away" };
+                let team_name = format!("({}) {} {}", line.team_abbreviation, line.team_city_name, line.team_name);
+                let line_score = match status {
+                    GameStatus::Pregame { .. } => None,
+                    _ => line.line_score(overtimes),
+                };
+                rows.push(ScoreboardRow {
+                    game_id: header.game_id.clone(),
+                    side,
+                    team: team_name,
+                    status: status.clone(),
+                    line_score,
+                });
+            }
+        }
+        Ok(scoreboard_table(rows))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::nba::event_codes::{ShotType, TurnoverType};
+        use serde_json::json;
+
+        fn live_team(tricode: &str, periods: &[i64]) -> Value {
+            json!({
+                "teamName": tricode,
+                "teamCity": "City",
+                "teamTricode": tricode,
+                "score": periods.iter().sum::<i64>(),
+                "periods": periods.iter().enumerate().map(|(pos, score)| json!({"period": pos + 1, "score": score})).collect::<Vec<_>>(),
+            })
+        }
+
+        fn live_game(game_id: &str, status: i64, status_text: &str, period: i64, clock: &str, home: Value, away: Value) -> Value {
+            json!({
+                "gameId": game_id,
+                "gameStatus": status,
+                "gameStatusText": status_text,
+                "period": period,
+                "gameClock": clock,
+                "homeTeam": home,
+                "awayTeam": away,
+            })
+        }
+
+        // A final that went to double overtime and a game in the third
+        fn live_scoreboard() -> Value {
+            json!({"scoreboard": {"games": [
+                live_game("0022100001", 3, "Final/OT2", 6, "", live_team("GSW", &[30, 25, 20, 25, 10, 12]), live_team("BOS", &[25, 25, 25, 25, 10, 8])),
+                live_game("0022100002", 2, "Q3 5:12", 3, "PT05M12.00S", live_team("LAL", &[30, 20, 10]), live_team("MIA", &[20, 22, 9])),
+            ]}})
+        }
+
+        #[test]
+        fn parses_live_clocks() {
+            assert_eq!(parse_duration_clock("PT02M41.00S").unwrap().to_string(), "2:41");
+            assert_eq!(parse_duration_clock("PT00M04.30S").unwrap().to_string(), "0:04.3");
+            assert_eq!(parse_duration_clock("PT12M00.00S").unwrap().seconds_remaining(), 720.0);
+            for clock in ["", "2:41", "PT02M41.00", "PTM41.00S"] {
+                assert_eq!(parse_duration_clock(clock), None, "{:?}", clock);
+            }
+        }
+
+        #[test]
+        fn live_scoreboard_has_a_column_per_overtime() {
+            let table = live_scoreboard_from_json(&live_scoreboard()).unwrap();
+            assert_eq!(table.columns, ["game_id", "side", "team", "status", "q1", "q2", "q3", "q4", "ot1", "ot2", "total"]);
+            assert_eq!(table.rows[0], vec![
+                json!("0022100001"), json!("home"), json!("(GSW) City GSW"), json!("Final/2OT"),
+                json!(30), json!(25), json!(20), json!(25), json!(10), json!(12), json!(122),
+            ]);
+            assert_eq!(table.rows[3], vec![
+                json!("0022100002"), json!("away"), json!("(MIA) City MIA"), json!("Q3 5:12"),
+                json!(20), json!(22), json!(9), Value::Null, Value::Null, Value::Null, json!(51),
+            ]);
+        }
+
+        #[test]
+        fn snapshots_of_the_live_scoreboard() {
+            let snapshots = scoreboard_snapshots(&live_scoreboard()).unwrap();
+            assert_eq!(snapshots[0].status_text, "Final/2OT");
+            assert_eq!(snapshots[0].margin(), 4);
+            assert_eq!(snapshots[1].period, 3);
+            assert_eq!(snapshots[1].clock, Some("5:12".parse().unwrap()));
+            assert_eq!(snapshots[1].score_line(), "MIA 51 - 60 LAL");
+        }
+
+        #[test]
+        fn stats_scoreboard_is_laid_out_like_the_live_one() {
+            let header = |game_id: &str, status: i64, text: &str, period: Option<i64>| {
+                json!(["2022-01-10T00:00:00", game_id, status, text, period, null, 1610612744])
+            };
+            let line = |game_id: &str, team_id: i64, tricode: &str, quarters: [i64; 4], ot1: Option<i64>, pts: i64| {
+                json!([game_id, team_id, tricode, "City", tricode, quarters[0], quarters[1], quarters[2], quarters[3], ot1, pts])
+            };
+            let json = json!({"resultSets": [
+                {
+                    "name": "GameHeader",
+                    "headers": ["GAME_DATE_EST", "GAME_ID", "GAME_STATUS_ID", "GAME_STATUS_TEXT", "LIVE_PERIOD", "LIVE_PC_TIME", "HOME_TEAM_ID"],
+                    // Listed once per broadcaster
+                    "rowSet": [header("0022100001", 3, "Final/OT", Some(5)), header("0022100001", 3, "Final/OT", Some(5)), header("0022100002", 1, "7:30 pm ET", None)],
+                },
+                {
+                    "name": "LineScore",
+                    "headers": ["GAME_ID", "TEAM_ID", "TEAM_ABBREVIATION", "TEAM_CITY_NAME", "TEAM_NAME", "PTS_QTR1", "PTS_QTR2", "PTS_QTR3", "PTS_QTR4", "PTS_OT1", "PTS"],
+                    "rowSet": [
+                        line("0022100001", 1610612738, "BOS", [25, 25, 25, 25], Some(8), 108),
+                        line("0022100001", 1610612744, "GSW", [30, 20, 25, 25], Some(12), 112),
+                        line("0022100002", 1610612747, "LAL", [0, 0, 0, 0], None, 0),
+                        line("0022100002", 1610612744, "GSW", [0, 0, 0, 0], None, 0),
+                    ],
+                },
+            ]});
+            let table = scoreboard_from_json(&json).unwrap();
+            assert_eq!(table.columns, ["game_id", "side", "team", "status", "q1", "q2", "q3", "q4", "ot1", "total"]);
+            assert_eq!(table.rows.len(), 4);
+            assert_eq!(table.rows[0], vec![
+                json!("0022100001"), json!("home"), json!("(GSW) City GSW"), json!("Final/OT"),
+                json!(30), json!(20), json!(25), json!(25), json!(12), json!(112),
+            ]);
+            assert_eq!(table.rows[1][1], "away");
+            // No scores before the game
+            assert!(table.rows[3][4..].iter().all(Value::is_null));
+        }
+
+        // GSW at home against BOS, a pull-up three, a blocked layup and a
+        // stolen pass
+        fn live_playbyplay() -> Value {
+            json!({"game": {"gameId": "0042100401", "actions": [
+                {
+                    "actionNumber": 4, "period": 1, "clock": "PT11M38.00S", "actionType": "3pt", "subType": "Jump Shot",
+                    "descriptor": "pullup", "shotResult": "Made", "location": "h", "teamId": 1610612744, "teamTricode": "GSW",
+                    "personId": 201939, "playerNameI": "S. Curry", "assistPersonId": 203110, "assistPlayerNameInitial": "D. Green",
+                    "scoreHome": "3", "scoreAway": "0", "description": "S. Curry 26' pullup 3PT (3 PTS) (D. Green 1 AST)",
+                },
+                {
+                    "actionNumber": 7, "period": 1, "clock": "PT11M15.00S", "actionType": "2pt", "subType": "Layup",
+                    "descriptor": "driving", "shotResult": "Missed", "location": "v", "teamId": 1610612738, "teamTricode": "BOS",
+                    "personId": 1628369, "playerNameI": "J. Tatum", "blockPersonId": 203110, "blockPlayerName": "Green",
+                    "description": "MISS J. Tatum driving Layup",
+                },
+                {
+                    "actionNumber": 8, "period": 1, "clock": "PT11M15.00S", "actionType": "block", "location": "h",
+                    "teamId": 1610612744, "teamTricode": "GSW", "personId": 203110, "description": "D. Green BLOCK (1 BLK)",
+                },
+                {
+                    "actionNumber": 9, "period": 5, "clock": "PT04M59.40S", "actionType": "turnover", "subType": "bad pass",
+                    "location": "v", "teamId": 1610612738, "teamTricode": "BOS", "personId": 1628369, "playerNameI": "J. Tatum",
+                    "stealPersonId": 201939, "stealPlayerName": "Curry", "description": "J. Tatum bad pass TURNOVER (1 TO)",
+                },
+            ]}})
+        }
+
+        #[test]
+        fn live_actions_read_as_events() {
+            let events = live_events_from_json(&live_playbyplay()).unwrap();
+            // The block is folded into the missed layup
+            assert_eq!(events.iter().map(|e| e.event_num).collect::<Vec<_>>(), [4, 7, 9]);
+
+            let three = &events[0];
+            assert_eq!(three.event_type, EventMsgType::MadeShot);
+            assert_eq!(three.action, EventAction::Shot(ShotType::PullUpJumpShot));
+            assert_eq!(three.clock.to_string(), "11:38");
+            assert_eq!(three.shot_value, Some(3));
+            assert_eq!((three.score, three.margin), (Some(Score { visitor: 0, home: 3 }), Some(3)));
+            assert!(three.home_description.is_some() && three.visitor_description.is_none());
+            assert_eq!(three.players[1].as_ref().map(|p| (p.id, p.role)), Some((203110, PersonRole::HomePlayer)));
+
+            let layup = &events[1];
+            assert_eq!((layup.event_type, layup.action), (EventMsgType::MissedShot, EventAction::Shot(ShotType::DrivingLayup)));
+            let blocker = layup.players[2].as_ref().unwrap();
+            assert_eq!((blocker.id, blocker.team_abbreviation.as_deref(), blocker.role), (203110, Some("GSW"), PersonRole::HomePlayer));
+
+            let turnover = &events[2];
+            assert_eq!(turnover.action, EventAction::Turnover(TurnoverType::BadPass));
+            assert_eq!((turnover.period, turnover.clock.to_string()), (5, "4:59.4".to_string()));
+            assert_eq!(turnover.players[1].as_ref().map(|p| p.id), Some(201939));
+        }
+
+        #[test]
+        fn rejects_clocks_of_another_format() {
+            let mut json = live_playbyplay();
+            json["game"]["actions"][0]["clock"] = json!("11:38");
+            assert!(live_events_from_json(&json).is_err());
+        }
+    }
@@ -1,4 +1,17 @@
 pub mod endpoints;
 pub mod params;
 pub mod live_data;
-pub mod db;
\ No newline at end of file
+pub mod db;
+pub mod transport;
+pub mod cache;
+pub mod error;
+pub mod throttle;
+pub mod events;
+pub mod event_codes;
+pub mod filter;
+pub mod warehouse;
+pub mod output;
+pub mod export;
+pub mod sync;
+pub mod watch;
+pub mod alerts;
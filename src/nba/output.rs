@@ -0,0 +1,301 @@
+use crate::nba::error::{NbaError, NbaResult};
+
+use polars::prelude::{AnyValue, DataFrame};
+use serde_json::{Map, Value};
+use std::{fmt, fs::{File, OpenOptions}, io::{self, BufWriter, Write}, path::{Path, PathBuf}, str::FromStr};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Table,
+    Csv,
+    Json,
+    Ndjson,
+    Markdown,
+}
+
+impl OutputFormat {
+    const ALL: [OutputFormat; 5] = [OutputFormat::Table, OutputFormat::Csv, OutputFormat::Json, OutputFormat::Ndjson, OutputFormat::Markdown];
+
+    fn name(&self) -> &'static str {
+        match self {
+            OutputFormat::Table => "table",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Json => "json",
+            OutputFormat::Ndjson => "ndjson",
+            OutputFormat::Markdown => "markdown",
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = NbaError;
+    fn from_str(s: &str) -> NbaResult<OutputFormat> {
+        OutputFormat::ALL.iter()
+            .find(|format| format.name().eq_ignore_ascii_case(s) || (*format == &OutputFormat::Markdown && s.eq_ignore_ascii_case("md")))
+            .copied()
+            .ok_or_else(|| NbaError::InvalidParam(format!(
+                "unknown format {}, expected one of {}",
+                s,
+                OutputFormat::ALL.iter().map(|format| format.name()).collect::<Vec<_>>().join(", "),
+            )))
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Named rows of json values, what every subcommand hands to `Output`.
+#[derive(Debug, Clone)]
+pub struct OutputTable {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+impl OutputTable {
+    pub fn new(name: &str, columns: &[&str]) -> OutputTable {
+        OutputTable {
+            name: name.to_string(),
+            columns: columns.iter().map(|col| col.to_string()).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn from_dataframe(name: &str, df: &DataFrame) -> OutputTable {
+        let mut table = OutputTable::new(name, &df.get_column_names());
+        for i in 0..df.height() {
+            if let Some(row) = df.get(i) {
+                table.rows.push(row.into_iter().map(any_value_to_json).collect());
+            }
+        }
+        table
+    }
+
+    /// One row per json object, columns taken from the first object's keys.
+    pub fn from_records(name: &str, records: Vec<Value>) -> OutputTable {
+        let columns: Vec<String> = records.first()
+            .and_then(|record| record.as_object())
+            .map(|record| record.keys().cloned().collect())
+            .unwrap_or_default();
+        let rows = records.iter()
+            .map(|record| columns.iter().map(|col| record[col.as_str()].clone()).collect())
+            .collect();
+        OutputTable { name: name.to_string(), columns, rows }
+    }
+
+    fn row_object(&self, row: &[Value]) -> Map<String, Value> {
+        self.columns.iter().cloned().zip(row.iter().cloned()).collect()
+    }
+}
+
+fn any_value_to_json(val: AnyValue) -> Value {
+    match val {
+        AnyValue::Null => Value::Null,
+        AnyValue::Boolean(b) => Value::Bool(b),
+        AnyValue::Utf8(s) => Value::String(s.to_string()),
+        AnyValue::UInt8(n) => n.into(),
+        AnyValue::UInt16(n) => n.into(),
+        AnyValue::UInt32(n) => n.into(),
+        AnyValue::UInt64(n) => n.into(),
+        AnyValue::Int8(n) => n.into(),
+        AnyValue::Int16(n) => n.into(),
+        AnyValue::Int32(n) => n.into(),
+        AnyValue::Int64(n) => n.into(),
+        AnyValue::Float32(n) => n.into(),
+        AnyValue::Float64(n) => n.into(),
+        other => Value::String(other.to_string()),
+    }
+}
+
+/// Where and how a subcommand's tables are written. Most subcommands write
+/// once, so a file given with `--output` holds everything it produced.
+pub struct Output {
+    pub format: OutputFormat,
+    path: Option<PathBuf>,
+    append: bool,
+}
+
+impl Output {
+    pub fn new(format: OutputFormat, path: Option<&str>) -> Output {
+        Output { format, path: path.map(PathBuf::from), append: false }
+    }
+
+    /// The same output, but every write is added to the end of the file
+    /// instead of replacing it, for commands that write as they go.
+    pub fn appending(self) -> Output {
+        Output { append: true, ..self }
+    }
+
+    /// Writes `tables` to the output file or stdout. CSV has no room for
+    /// several tables in one file, with several each goes to its own
+    /// `<file stem>.<table name>.csv`.
+    pub fn write(&self, tables: &[OutputTable]) -> NbaResult<()> {
+        match &self.path {
+            Some(path) if self.format == OutputFormat::Csv && tables.len() > 1 => {
+                for table in tables {
+                    let table_path = path.with_extension(format!("{}.csv", table.name.to_lowercase()));
+                    self.write_file(&table_path, std::slice::from_ref(table))?;
+                }
+                Ok(())
+            }
+            Some(path) => self.write_file(path, tables),
+            None => {
+                let stdout = io::stdout();
+                let mut out = stdout.lock();
+                match self.render(tables, &mut out).and_then(|_| Ok(out.flush()?)) {
+                    // Piped into something like head that stopped reading
+                    Err(NbaError::Io(e)) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+                    written => written,
+                }
+            }
+        }
+    }
+
+    fn write_file(&self, path: &Path, tables: &[OutputTable]) -> NbaResult<()> {
+        let file = if self.append {
+            OpenOptions::new().create(true).append(true).open(path)?
+        } else {
+            File::create(path)?
+        };
+        let mut out = BufWriter::new(file);
+        self.render(tables, &mut out)?;
+        Ok(out.flush()?)
+    }
+
+    // Table names are only written when there's more than one table
+    fn render(&self, tables: &[OutputTable], out: &mut dyn Write) -> NbaResult<()> {
+        let named = tables.len() > 1;
+        match self.format {
+            OutputFormat::Json => {
+                let json = if named {
+                    Value::Object(tables.iter()
+                        .map(|table| (table.name.clone(), table_json(table)))
+                        .collect())
+                } else {
+                    tables.first().map(table_json).unwrap_or_else(|| Value::Array(Vec::new()))
+                };
+                serde_json::to_writer_pretty(&mut *out, &json)?;
+                writeln!(out)?;
+            }
+            OutputFormat::Ndjson => {
+                for table in tables {
+                    for row in &table.rows {
+                        let mut object = table.row_object(row);
+                        if named {
+                            object.insert("result_set".to_string(), Value::String(table.name.clone()));
+                        }
+                        writeln!(out, "{}", Value::Object(object))?;
+                    }
+                }
+            }
+            OutputFormat::Csv => {
+                for (pos, table) in tables.iter().enumerate() {
+                    if pos > 0 {
+                        writeln!(out)?;
+                    }
+                    write_csv(table, out)?;
+                }
+            }
+            OutputFormat::Markdown => {
+                for (pos, table) in tables.iter().enumerate() {
+                    if pos > 0 {
+                        writeln!(out)?;
+                    }
+                    if named {
+                        writeln!(out, "### {}\n", table.name)?;
+                    }
+                    write_markdown(table, out)?;
+                }
+            }
+            OutputFormat::Table => {
+                for table in tables {
+                    if named {
+                        writeln!(out, "{}", table.name)?;
+                    }
+                    write_text_table(table, out)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Adds `table` to `tables`, appending its rows to a table of the same name
+/// and columns if there is one, e.g. the same result set of several games.
+pub fn merge_table(tables: &mut Vec<OutputTable>, table: OutputTable) {
+    match tables.iter_mut().find(|t| t.name == table.name && t.columns == table.columns) {
+        Some(existing) => existing.rows.extend(table.rows),
+        None => tables.push(table),
+    }
+}
+
+fn table_json(table: &OutputTable) -> Value {
+    Value::Array(table.rows.iter().map(|row| Value::Object(table.row_object(row))).collect())
+}
+
+// How a value reads in a text cell, nulls are left empty
+fn cell_text(val: &Value) -> String {
+    match val {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn write_csv(table: &OutputTable, out: &mut dyn Write) -> io::Result<()> {
+    let csv_field = |field: &str| {
+        if field.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field.to_string()
+        }
+    };
+    writeln!(out, "{}", table.columns.iter().map(|col| csv_field(col)).collect::<Vec<_>>().join(","))?;
+    for row in &table.rows {
+        writeln!(out, "{}", row.iter().map(|val| csv_field(&cell_text(val))).collect::<Vec<_>>().join(","))?;
+    }
+    Ok(())
+}
+
+fn write_markdown(table: &OutputTable, out: &mut dyn Write) -> io::Result<()> {
+    let md_cell = |cell: &str| cell.replace('|', "\\|").replace('\n', " ");
+    writeln!(out, "| {} |", table.columns.iter().map(|col| md_cell(col)).collect::<Vec<_>>().join(" | "))?;
+    writeln!(out, "|{}|", vec!["---"; table.columns.len()].join("|"))?;
+    for row in &table.rows {
+        writeln!(out, "| {} |", row.iter().map(|val| md_cell(&cell_text(val))).collect::<Vec<_>>().join(" | "))?;
+    }
+    Ok(())
+}
+
+fn write_text_table(table: &OutputTable, out: &mut dyn Write) -> io::Result<()> {
+    let cells: Vec<Vec<String>> = table.rows.iter()
+        .map(|row| row.iter().map(|val| cell_text(val).replace('\n', " ")).collect())
+        .collect();
+    let mut widths: Vec<usize> = table.columns.iter().map(|col| col.chars().count()).collect();
+    for row in &cells {
+        for (col, cell) in row.iter().enumerate().take(widths.len()) {
+            widths[col] = widths[col].max(cell.chars().count());
+        }
+    }
+    let border = format!("+{}+", widths.iter().map(|w| "-".repeat(w + 2)).collect::<Vec<_>>().join("+"));
+    let line = |row: &[String]| {
+        let padded: Vec<String> = widths.iter().enumerate()
+            .map(|(col, w)| {
+                let cell = row.get(col).map(|c| c.as_str()).unwrap_or("");
+                format!(" {}{} ", cell, " ".repeat(w - cell.chars().count()))
+            })
+            .collect();
+        format!("|{}|", padded.join("|"))
+    };
+    writeln!(out, "{}", border)?;
+    writeln!(out, "{}", line(&table.columns))?;
+    writeln!(out, "{}", border)?;
+    for row in &cells {
+        writeln!(out, "{}", line(row))?;
+    }
+    writeln!(out, "{}", border)?;
+    Ok(())
+}
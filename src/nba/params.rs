@@ -1,94 +1,536 @@
+    use std::str::FromStr;
+    use crate::nba::error::{NbaError, NbaResult};
+    use chrono::NaiveDate;
 
-    use core::fmt;
-    use std::{fmt::Display};
-
+    /// A single stats.nba.com query parameter, validated before it is put on the wire.
     pub trait NBAParam {
-        fn get_formatted_param(&self) -> ();
+        fn get_formatted_param(&self) -> NbaResult<(String, String)>;
     }
 
+    #[derive(Clone, Debug)]
     pub enum LastNGames {
         N(i32)
     }
 
+    #[derive(Clone, Debug)]
     pub enum GameID {
         ID(String)
     }
 
+    #[derive(Clone, Debug)]
+    pub enum GameEventID {
+        ID(i64)
+    }
+
+    #[derive(Clone, Debug)]
     pub enum Period {
-        P(i8) 
+        P(i8)
     }
 
+    #[allow(clippy::upper_case_acronyms)]
+    #[derive(Clone, Debug, Default)]
     pub enum LeagueID {
+        #[default]
         NBA,
+        WNBA,
+        GLeague,
     }
 
+    #[derive(Clone, Debug)]
     pub enum Season {
         S(String)
     }
 
-    pub struct StartPeriod(Period);
-    pub struct EndPeriod(Period);
-    
+    #[derive(Clone, Debug, Default)]
+    pub enum SeasonType {
+        #[default]
+        RegularSeason,
+        PreSeason,
+        Playoffs,
+        PlayIn,
+        AllStar,
+    }
+
+    #[derive(Clone, Debug, Default)]
+    pub enum PerMode {
+        #[default]
+        Totals,
+        PerGame,
+        Per36,
+        Per48,
+        Per100Possessions,
+        Per100Plays,
+        PerMinute,
+        PerPossession,
+        PerPlay,
+    }
+
+    #[derive(Clone, Debug, Default)]
+    pub enum MeasureType {
+        #[default]
+        Base,
+        Advanced,
+        Misc,
+        FourFactors,
+        Scoring,
+        Opponent,
+        Usage,
+        Defense,
+    }
+
+    #[derive(Clone, Debug, Default)]
+    pub enum Location {
+        #[default]
+        Any,
+        Home,
+        Road,
+    }
+
+    #[derive(Clone, Debug, Default)]
+    pub enum Outcome {
+        #[default]
+        Any,
+        Win,
+        Loss,
+    }
+
+    #[derive(Clone, Debug, Default)]
+    pub enum TeamID {
+        #[default]
+        Any,
+        ID(i64)
+    }
+
+    #[derive(Clone, Debug)]
+    pub enum PlayerID {
+        ID(i64)
+    }
+
+    #[derive(Clone, Debug, Default)]
+    pub enum PlayerOrTeam {
+        #[default]
+        Player,
+        Team,
+    }
+
+    #[derive(Clone, Debug)]
+    pub enum RangeType {
+        R(i8)
+    }
+
+    #[derive(Clone, Debug)]
+    pub struct StartPeriod(pub Period);
+    #[derive(Clone, Debug)]
+    pub struct EndPeriod(pub Period);
+    /// Start of a time range in tenths of a second from tip off.
+    #[derive(Clone, Debug, Default)]
+    pub struct StartRange(pub i64);
+    /// End of a time range in tenths of a second from tip off.
+    #[derive(Clone, Debug, Default)]
+    pub struct EndRange(pub i64);
+    #[derive(Clone, Debug, Default)]
+    pub struct DateFrom(pub Option<NaiveDate>);
+    #[derive(Clone, Debug, Default)]
+    pub struct DateTo(pub Option<NaiveDate>);
+    #[derive(Clone, Debug, Default)]
+    pub struct IsOnlyCurrentSeason(pub bool);
+    #[derive(Clone, Debug)]
+    pub struct GameDate(pub NaiveDate);
+    /// Days added to `GameDate`.
+    #[derive(Clone, Debug, Default)]
+    pub struct DayOffset(pub i64);
+
+    /// Any param without a typed counterpart, passed through untouched.
+    #[derive(Clone, Debug)]
+    pub struct RawParam {
+        pub key: String,
+        pub value: String,
+    }
+
+    fn param(key: &str, value: impl ToString) -> NbaResult<(String, String)> {
+        Ok((key.to_string(), value.to_string()))
+    }
+
+    fn format_date(key: &str, date: &Option<NaiveDate>) -> NbaResult<(String, String)> {
+        match date {
+            Some(d) => param(key, d.format("%m/%d/%Y")),
+            None => param(key, ""),
+        }
+    }
+
+    fn check_period(key: &str, period: &Period) -> NbaResult<(String, String)> {
+        let Period::P(period_num) = period;
+        // 0 means every period, anything above 4 is an overtime
+        if !(0..=14).contains(period_num) {
+            return Err(NbaError::InvalidParam(format!("{} must be between 0 and 14, got {}", key, period_num)));
+        }
+        param(key, period_num)
+    }
+
+    impl NBAParam for LastNGames {
+        fn get_formatted_param(&self) -> NbaResult<(String, String)> {
+            let LastNGames::N(n) = self;
+            if *n < 0 {
+                return Err(NbaError::InvalidParam(format!("LastNGames can't be negative, got {}", n)));
+            }
+            param("LastNGames", n)
+        }
+    }
+
+    impl NBAParam for GameID {
+        fn get_formatted_param(&self) -> NbaResult<(String, String)> {
+            let GameID::ID(id) = self;
+            if id.len() != 10 || !id.chars().all(|c| c.is_ascii_digit()) {
+                return Err(NbaError::InvalidParam(format!("GameID must be 10 digits like 0042100401, got {:?}", id)));
+            }
+            param("GameID", id)
+        }
+    }
+
+    impl NBAParam for GameEventID {
+        fn get_formatted_param(&self) -> NbaResult<(String, String)> {
+            let GameEventID::ID(id) = self;
+            if *id < 0 {
+                return Err(NbaError::InvalidParam(format!("GameEventID can't be negative, got {}", id)));
+            }
+            param("GameEventID", id)
+        }
+    }
+
+    impl NBAParam for Period {
+        fn get_formatted_param(&self) -> NbaResult<(String, String)> {
+            check_period("Period", self)
+        }
+    }
+
+    impl NBAParam for StartPeriod {
+        fn get_formatted_param(&self) -> NbaResult<(String, String)> {
+            check_period("StartPeriod", &self.0)
+        }
+    }
+
+    impl NBAParam for EndPeriod {
+        fn get_formatted_param(&self) -> NbaResult<(String, String)> {
+            check_period("EndPeriod", &self.0)
+        }
+    }
+
+    impl NBAParam for StartRange {
+        fn get_formatted_param(&self) -> NbaResult<(String, String)> {
+            if self.0 < 0 {
+                return Err(NbaError::InvalidParam(format!("StartRange can't be negative, got {}", self.0)));
+            }
+            param("StartRange", self.0)
+        }
+    }
+
+    impl NBAParam for EndRange {
+        fn get_formatted_param(&self) -> NbaResult<(String, String)> {
+            if self.0 < 0 {
+                return Err(NbaError::InvalidParam(format!("EndRange can't be negative, got {}", self.0)));
+            }
+            param("EndRange", self.0)
+        }
+    }
+
+    impl NBAParam for RangeType {
+        fn get_formatted_param(&self) -> NbaResult<(String, String)> {
+            let RangeType::R(range_type) = self;
+            if !(0..=2).contains(range_type) {
+                return Err(NbaError::InvalidParam(format!("RangeType must be 0, 1 or 2, got {}", range_type)));
+            }
+            param("RangeType", range_type)
+        }
+    }
+
+    impl NBAParam for Season {
+        fn get_formatted_param(&self) -> NbaResult<(String, String)> {
+            let Season::S(season) = self;
+            let years = season.split_once('-')
+                .and_then(|(first, second)| Some((first.parse::<i32>().ok()?, second.parse::<i32>().ok()?)))
+                .filter(|(first, second)| season.len() == 7 && (first + 1) % 100 == *second);
+            if years.is_none() {
+                return Err(NbaError::InvalidParam(format!("Season must look like 2021-22, got {:?}", season)));
+            }
+            param("Season", season)
+        }
+    }
 
-    impl Display for GameID {
-        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    impl NBAParam for LeagueID {
+        fn get_formatted_param(&self) -> NbaResult<(String, String)> {
             match self {
-                GameID::ID(id) => {
-                    write!(f, "GameID={}", id)
-                }
+                LeagueID::NBA => param("LeagueID", "00"),
+                LeagueID::WNBA => param("LeagueID", "10"),
+                LeagueID::GLeague => param("LeagueID", "20"),
             }
         }
     }
 
-    impl Display for Period {
-        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    impl NBAParam for SeasonType {
+        fn get_formatted_param(&self) -> NbaResult<(String, String)> {
             match self {
-                Period::P(period_num) => write!(f, "Period={}", period_num)
+                SeasonType::RegularSeason => param("SeasonType", "Regular Season"),
+                SeasonType::PreSeason => param("SeasonType", "Pre Season"),
+                SeasonType::Playoffs => param("SeasonType", "Playoffs"),
+                SeasonType::PlayIn => param("SeasonType", "PlayIn"),
+                SeasonType::AllStar => param("SeasonType", "All Star"),
             }
-            
         }
     }
 
-    impl Display for StartPeriod {
-        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-            match self.0 {
-                Period::P(period_num) => write!(f, "StartPeriod={}",period_num)
+    impl NBAParam for PerMode {
+        fn get_formatted_param(&self) -> NbaResult<(String, String)> {
+            param("PerMode", format!("{:?}", self))
+        }
+    }
+
+    impl NBAParam for MeasureType {
+        fn get_formatted_param(&self) -> NbaResult<(String, String)> {
+            match self {
+                MeasureType::FourFactors => param("MeasureType", "Four Factors"),
+                measure_type => param("MeasureType", format!("{:?}", measure_type)),
             }
-            
         }
     }
 
-    impl Display for EndPeriod {
-        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-            match self.0 {
-                Period::P(period_num) => write!(f, "EndPeriod={}",period_num)
+    impl NBAParam for Location {
+        fn get_formatted_param(&self) -> NbaResult<(String, String)> {
+            match self {
+                Location::Any => param("Location", ""),
+                Location::Home => param("Location", "Home"),
+                Location::Road => param("Location", "Road"),
             }
-            
         }
     }
 
-    impl Display for Season {
-        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    impl NBAParam for Outcome {
+        fn get_formatted_param(&self) -> NbaResult<(String, String)> {
             match self {
-                Season::S(season) => write!(f, "Season={}", season)
+                Outcome::Any => param("Outcome", ""),
+                Outcome::Win => param("Outcome", "W"),
+                Outcome::Loss => param("Outcome", "L"),
             }
         }
     }
 
-    impl Display for LeagueID {
-        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    impl NBAParam for TeamID {
+        fn get_formatted_param(&self) -> NbaResult<(String, String)> {
             match self {
-                LeagueID::NBA => write!(f, "LeagueID=00")
+                TeamID::Any => param("TeamID", 0),
+                TeamID::ID(id) if *id > 0 => param("TeamID", id),
+                TeamID::ID(id) => Err(NbaError::InvalidParam(format!("TeamID must be positive, got {}", id))),
+            }
+        }
+    }
+
+    impl NBAParam for PlayerID {
+        fn get_formatted_param(&self) -> NbaResult<(String, String)> {
+            let PlayerID::ID(id) = self;
+            if *id <= 0 {
+                return Err(NbaError::InvalidParam(format!("PlayerID must be positive, got {}", id)));
             }
+            param("PlayerID", id)
+        }
+    }
+
+    impl NBAParam for PlayerOrTeam {
+        fn get_formatted_param(&self) -> NbaResult<(String, String)> {
+            match self {
+                PlayerOrTeam::Player => param("PlayerOrTeam", "P"),
+                PlayerOrTeam::Team => param("PlayerOrTeam", "T"),
+            }
+        }
+    }
+
+    impl NBAParam for DateFrom {
+        fn get_formatted_param(&self) -> NbaResult<(String, String)> {
+            format_date("DateFrom", &self.0)
         }
     }
 
-    impl Default for LeagueID {
-        fn default() -> Self { LeagueID::NBA }
+    impl NBAParam for DateTo {
+        fn get_formatted_param(&self) -> NbaResult<(String, String)> {
+            format_date("DateTo", &self.0)
+        }
+    }
+
+    impl NBAParam for GameDate {
+        fn get_formatted_param(&self) -> NbaResult<(String, String)> {
+            format_date("GameDate", &Some(self.0))
+        }
+    }
+
+    impl NBAParam for DayOffset {
+        fn get_formatted_param(&self) -> NbaResult<(String, String)> {
+            param("DayOffset", self.0)
+        }
+    }
+
+    impl NBAParam for IsOnlyCurrentSeason {
+        fn get_formatted_param(&self) -> NbaResult<(String, String)> {
+            param("IsOnlyCurrentSeason", self.0 as i8)
+        }
+    }
+
+    impl NBAParam for RawParam {
+        fn get_formatted_param(&self) -> NbaResult<(String, String)> {
+            param(&self.key, &self.value)
+        }
+    }
+
+    fn parse_date(date: &str) -> NbaResult<Option<NaiveDate>> {
+        if date.is_empty() {
+            return Ok(None);
+        }
+        NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .or_else(|_| NaiveDate::parse_from_str(date, "%m/%d/%Y"))
+            .map(Some)
+            .map_err(|_| NbaError::InvalidParam(format!("expected a date like 2022-05-29, got {:?}", date)))
+    }
+
+    fn parse_num<T: FromStr>(key: &str, value: &str) -> NbaResult<T> {
+        value.parse::<T>().map_err(|_| NbaError::InvalidParam(format!("{} expects a number, got {:?}", key, value)))
+    }
+
+    impl FromStr for SeasonType {
+        type Err = NbaError;
+        fn from_str(s: &str) -> NbaResult<Self> {
+            match s.replace(['+', ' '], "").to_lowercase().as_str() {
+                "regularseason" | "regular" => Ok(SeasonType::RegularSeason),
+                "preseason" => Ok(SeasonType::PreSeason),
+                "playoffs" => Ok(SeasonType::Playoffs),
+                "playin" => Ok(SeasonType::PlayIn),
+                "allstar" => Ok(SeasonType::AllStar),
+                _ => Err(NbaError::InvalidParam(format!("unknown SeasonType {:?}", s))),
+            }
+        }
+    }
+
+    impl FromStr for PerMode {
+        type Err = NbaError;
+        fn from_str(s: &str) -> NbaResult<Self> {
+            match s.to_lowercase().as_str() {
+                "totals" => Ok(PerMode::Totals),
+                "pergame" => Ok(PerMode::PerGame),
+                "per36" => Ok(PerMode::Per36),
+                "per48" => Ok(PerMode::Per48),
+                "per100possessions" => Ok(PerMode::Per100Possessions),
+                "per100plays" => Ok(PerMode::Per100Plays),
+                "perminute" => Ok(PerMode::PerMinute),
+                "perpossession" => Ok(PerMode::PerPossession),
+                "perplay" => Ok(PerMode::PerPlay),
+                _ => Err(NbaError::InvalidParam(format!("unknown PerMode {:?}", s))),
+            }
+        }
+    }
+
+    impl FromStr for MeasureType {
+        type Err = NbaError;
+        fn from_str(s: &str) -> NbaResult<Self> {
+            match s.replace(['+', ' '], "").to_lowercase().as_str() {
+                "base" => Ok(MeasureType::Base),
+                "advanced" => Ok(MeasureType::Advanced),
+                "misc" => Ok(MeasureType::Misc),
+                "fourfactors" => Ok(MeasureType::FourFactors),
+                "scoring" => Ok(MeasureType::Scoring),
+                "opponent" => Ok(MeasureType::Opponent),
+                "usage" => Ok(MeasureType::Usage),
+                "defense" => Ok(MeasureType::Defense),
+                _ => Err(NbaError::InvalidParam(format!("unknown MeasureType {:?}", s))),
+            }
+        }
+    }
+
+    impl FromStr for Location {
+        type Err = NbaError;
+        fn from_str(s: &str) -> NbaResult<Self> {
+            match s.to_lowercase().as_str() {
+                "" => Ok(Location::Any),
+                "home" => Ok(Location::Home),
+                "road" | "away" => Ok(Location::Road),
+                _ => Err(NbaError::InvalidParam(format!("unknown Location {:?}", s))),
+            }
+        }
+    }
+
+    impl FromStr for Outcome {
+        type Err = NbaError;
+        fn from_str(s: &str) -> NbaResult<Self> {
+            match s.to_lowercase().as_str() {
+                "" => Ok(Outcome::Any),
+                "w" | "win" => Ok(Outcome::Win),
+                "l" | "loss" => Ok(Outcome::Loss),
+                _ => Err(NbaError::InvalidParam(format!("unknown Outcome {:?}", s))),
+            }
+        }
+    }
+
+    impl FromStr for LeagueID {
+        type Err = NbaError;
+        fn from_str(s: &str) -> NbaResult<Self> {
+            match s.to_lowercase().as_str() {
+                "00" | "nba" => Ok(LeagueID::NBA),
+                "10" | "wnba" => Ok(LeagueID::WNBA),
+                "20" | "gleague" => Ok(LeagueID::GLeague),
+                _ => Err(NbaError::InvalidParam(format!("unknown LeagueID {:?}", s))),
+            }
+        }
+    }
+
+    impl FromStr for PlayerOrTeam {
+        type Err = NbaError;
+        fn from_str(s: &str) -> NbaResult<Self> {
+            match s.to_lowercase().as_str() {
+                "p" | "player" => Ok(PlayerOrTeam::Player),
+                "t" | "team" => Ok(PlayerOrTeam::Team),
+                _ => Err(NbaError::InvalidParam(format!("unknown PlayerOrTeam {:?}", s))),
+            }
+        }
+    }
+
+    /// Parses a `Key=Value` pair from the command line into its typed param so
+    /// bad values are rejected before a request is made. Unknown keys are
+    /// passed through as a `RawParam`.
+    pub fn parse_param(key: &str, value: &str) -> NbaResult<Box<dyn NBAParam>> {
+        let typed_param: Box<dyn NBAParam> = match key.to_lowercase().as_str() {
+            "gameid" => Box::new(GameID::ID(value.to_string())),
+            "gameeventid" => Box::new(GameEventID::ID(parse_num(key, value)?)),
+            "season" => Box::new(Season::S(value.to_string())),
+            "seasontype" => Box::new(value.parse::<SeasonType>()?),
+            "leagueid" => Box::new(value.parse::<LeagueID>()?),
+            "permode" => Box::new(value.parse::<PerMode>()?),
+            "measuretype" => Box::new(value.parse::<MeasureType>()?),
+            "location" => Box::new(value.parse::<Location>()?),
+            "outcome" => Box::new(value.parse::<Outcome>()?),
+            "playerorteam" => Box::new(value.parse::<PlayerOrTeam>()?),
+            "teamid" => match parse_num::<i64>(key, value)? {
+                0 => Box::new(TeamID::Any),
+                id => Box::new(TeamID::ID(id)),
+            },
+            "playerid" => Box::new(PlayerID::ID(parse_num(key, value)?)),
+            "lastngames" => Box::new(LastNGames::N(parse_num(key, value)?)),
+            "period" => Box::new(Period::P(parse_num(key, value)?)),
+            "startperiod" => Box::new(StartPeriod(Period::P(parse_num(key, value)?))),
+            "endperiod" => Box::new(EndPeriod(Period::P(parse_num(key, value)?))),
+            "startrange" => Box::new(StartRange(parse_num(key, value)?)),
+            "endrange" => Box::new(EndRange(parse_num(key, value)?)),
+            "rangetype" => Box::new(RangeType::R(parse_num(key, value)?)),
+            "datefrom" => Box::new(DateFrom(parse_date(value)?)),
+            "dateto" => Box::new(DateTo(parse_date(value)?)),
+            "gamedate" => match parse_date(value)? {
+                Some(date) => Box::new(GameDate(date)),
+                None => Box::new(RawParam { key: key.to_string(), value: String::new() }),
+            },
+            "dayoffset" => Box::new(DayOffset(parse_num(key, value)?)),
+            "isonlycurrentseason" => Box::new(IsOnlyCurrentSeason(value == "1" || value.eq_ignore_ascii_case("true"))),
+            _ => Box::new(RawParam { key: key.to_string(), value: value.to_string() }),
+        };
+        typed_param.get_formatted_param()?;
+        Ok(typed_param)
     }
 
     impl Default for Season {
-        fn default() -> Self { 
+        fn default() -> Self {
             let current_date = chrono::Utc::now();
             let next_date = current_date - chrono::Duration::days(365);
             let first_year = next_date.format("%Y").to_string();
@@ -116,3 +558,7 @@
     impl Default for LastNGames {
         fn default() -> Self { LastNGames::N(0) }
     }
+
+    impl Default for RangeType {
+        fn default() -> Self { RangeType::R(0) }
+    }
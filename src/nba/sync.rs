@@ -0,0 +1,180 @@
+use crate::nba::db::SaveToDB;
+use crate::nba::endpoints::{AsyncNBAEndpoint, BoxScoreDefensive, BoxScoreSummaryV2, LeagueGameLog, PlayByPlayV2};
+use crate::nba::error::{NbaError, NbaResult};
+use crate::nba::output::OutputTable;
+use crate::nba::params::{GameID, LeagueID, NBAParam, Season, SeasonType};
+use crate::nba::transport::Transport;
+use crate::nba::warehouse::Warehouse;
+
+use serde_json::Value;
+use std::sync::Arc;
+
+/// What is ingested for every game, in the order it's recorded.
+pub const SYNC_STEPS: [&str; 3] = ["summary", "boxscore", "playbyplay"];
+
+/// Per game outcome of a sync, and the first error if a game failed.
+pub struct SyncReport {
+    pub games: OutputTable,
+    pub first_error: Option<NbaError>,
+}
+
+fn step_endpoint(step: &str, game_id: &str, transport: &Arc<dyn Transport>, warehouse: &Warehouse) -> NbaResult<Box<dyn SaveToDB>> {
+    let game_id = GameID::ID(game_id.to_string());
+    let endpoint: Box<dyn SaveToDB> = match step {
+        "summary" => Box::new(BoxScoreSummaryV2::new(game_id, Arc::clone(transport), warehouse.clone())?),
+        "boxscore" => Box::new(BoxScoreDefensive::new(game_id, Arc::clone(transport), warehouse.clone())?),
+        "playbyplay" => Box::new(PlayByPlayV2::new(
+            Default::default(),
+            Default::default(),
+            game_id,
+            Default::default(),
+            Arc::clone(transport),
+            warehouse.clone(),
+        )?),
+        _ => return Err(NbaError::InvalidParam(format!("unknown sync step {}", step))),
+    };
+    Ok(endpoint)
+}
+
+/// Finds every game of a season in its game log and stores the summary, box
+/// score and play by play of each. Steps already recorded in the warehouse's
+/// `sync_checkpoint` are skipped unless `resync`, so running it again after
+/// an interruption picks up where it stopped. A game that fails doesn't stop
+/// the others, it's retried on the next run.
+pub fn sync_season(transport: &Arc<dyn Transport>, warehouse: &Warehouse, season: Season, season_type: SeasonType, resync: bool) -> NbaResult<SyncReport> {
+    let (_, season_name) = season.get_formatted_param()?;
+    let (_, season_type_name) = season_type.get_formatted_param()?;
+    let game_log = LeagueGameLog::new(LeagueID::NBA, season, season_type, Arc::clone(transport), warehouse.clone())?;
+    let games = game_log.games()?;
+    eprintln!("{} {} has {} games", season_name, season_type_name, games.len());
+
+    let runtime = tokio::runtime::Runtime::new()?;
+    let mut report = SyncReport {
+        games: OutputTable::new("Sync", &["game_id", "game_date", "synced", "skipped", "error"]),
+        first_error: None,
+    };
+    for (pos, game) in games.iter().enumerate() {
+        let mut pending_steps = Vec::new();
+        let mut skipped = Vec::new();
+        for step in SYNC_STEPS {
+            if !resync && warehouse.is_synced(&game.game_id, step)? {
+                skipped.push(step);
+            } else {
+                pending_steps.push(step);
+            }
+        }
+        let mut endpoints = Vec::with_capacity(pending_steps.len());
+        for step in &pending_steps {
+            endpoints.push(step_endpoint(step, &game.game_id, transport, warehouse)?);
+        }
+        // A game's steps are fetched together, the transport keeps them within the rate limit
+        let results = runtime.block_on(futures::future::join_all(
+            endpoints.iter().map(|endpoint| endpoint.save_to_db_file_async())
+        ));
+        let mut synced = Vec::new();
+        let mut game_error = None;
+        for (step, result) in pending_steps.into_iter().zip(results) {
+            match result {
+                Ok(()) => {
+                    warehouse.record_sync(&season_name, &season_type_name, &game.game_id, step)?;
+                    synced.push(step);
+                }
+                Err(e) => {
+                    eprintln!("{} {} failed: {}", game.game_id, step, e);
+                    game_error.get_or_insert(e);
+                }
+            }
+        }
+        eprintln!("{}/{} {} done", pos + 1, games.len(), game.game_id);
+        report.games.rows.push(vec![
+            game.game_id.clone().into(),
+            game.game_date.clone().into(),
+            synced.join(",").into(),
+            skipped.join(",").into(),
+            game_error.as_ref().map_or(Value::Null, |e| e.to_string().into()),
+        ]);
+        if let Some(e) = game_error {
+            report.first_error.get_or_insert(e);
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    // Serves a game log of two games and an empty response to anything
+    // else, noting the endpoint of every request
+    #[derive(Default)]
+    struct SeasonTransport {
+        requested: Mutex<Vec<String>>,
+    }
+
+    impl SeasonTransport {
+        fn take_requested(&self) -> Vec<String> {
+            std::mem::take(&mut *self.requested.lock().unwrap())
+        }
+    }
+
+    impl Transport for SeasonTransport {
+        fn stats_base_url(&self) -> &str {
+            "https://stats.nba.com/stats"
+        }
+        fn cdn_base_url(&self) -> &str {
+            "https://cdn.nba.com"
+        }
+        fn get_json(&self, url: &str) -> NbaResult<Value> {
+            let endpoint = url.split('?').next().unwrap_or_default().rsplit('/').next().unwrap_or_default();
+            let game_id = url.split(['?', '&']).find_map(|param| param.strip_prefix("GameID=")).unwrap_or_default();
+            self.requested.lock().unwrap().push(format!("{} {}", endpoint, game_id).trim().to_string());
+            if endpoint == "leaguegamelog" {
+                return Ok(json!({"resultSets": [{
+                    "name": "LeagueGameLog",
+                    "headers": ["GAME_ID", "GAME_DATE"],
+                    "rowSet": [["0022100001", "2021-10-19"], ["0022100002", "2021-10-19"]],
+                }]}));
+            }
+            Ok(json!({"resultSets": []}))
+        }
+        fn get_bytes(&self, url: &str) -> NbaResult<Vec<u8>> {
+            Err(NbaError::InvalidParam(format!("unexpected request for {}", url)))
+        }
+    }
+
+    fn sync(transport: &Arc<SeasonTransport>, warehouse: &Warehouse, resync: bool) -> Vec<(String, String)> {
+        let transport: Arc<dyn Transport> = transport.clone();
+        let report = sync_season(&transport, warehouse, Season::S("2021-22".to_string()), SeasonType::RegularSeason, resync).unwrap();
+        assert!(report.first_error.is_none());
+        report.games.rows.iter()
+            .map(|row| (row[2].as_str().unwrap().to_string(), row[3].as_str().unwrap().to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn completed_steps_are_skipped() {
+        let transport = Arc::new(SeasonTransport::default());
+        let warehouse = Warehouse::open(":memory:", None).unwrap();
+        warehouse.record_sync("2021-22", "Regular Season", "0022100001", "summary").unwrap();
+
+        assert_eq!(sync(&transport, &warehouse, false), [
+            ("boxscore,playbyplay".to_string(), "summary".to_string()),
+            ("summary,boxscore,playbyplay".to_string(), "".to_string()),
+        ]);
+        let mut requested = transport.take_requested();
+        requested.sort();
+        assert_eq!(requested, [
+            "boxscoreplayertrackv2 0022100001", "boxscoreplayertrackv2 0022100002", "boxscoresummaryv2 0022100002",
+            "leaguegamelog", "playbyplayv2 0022100001", "playbyplayv2 0022100002",
+        ]);
+
+        // Run again after it finished, only the game log is fetched
+        assert!(sync(&transport, &warehouse, false).iter().all(|(synced, skipped)| synced.is_empty() && skipped == "summary,boxscore,playbyplay"));
+        assert_eq!(transport.take_requested(), ["leaguegamelog"]);
+
+        assert!(sync(&transport, &warehouse, true).iter().all(|(synced, _)| synced == "summary,boxscore,playbyplay"));
+        assert_eq!(transport.take_requested().len(), 7);
+    }
+}
@@ -0,0 +1,116 @@
+use crate::nba::transport::{Conditional, Transport, Validators};
+use crate::nba::error::{NbaError, NbaResult};
+
+use std::{sync::{Arc, Mutex}, thread, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
+use serde_json::Value;
+
+pub const DEFAULT_REQUESTS_PER_SECOND: f64 = 1.0;
+pub const DEFAULT_MAX_RETRIES: u32 = 4;
+
+const BASE_RETRY_DELAY: Duration = Duration::from_millis(500);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Wraps another transport so stats.nba.com sees at most `requests_per_second`
+/// from us, and retries timeouts, 429s and 5xx with exponential backoff.
+/// Shared between threads, the rate limit holds across all of them.
+pub struct ThrottledTransport {
+    inner: Arc<dyn Transport>,
+    min_interval: Duration,
+    next_request_at: Mutex<Instant>,
+    max_retries: u32,
+}
+
+impl ThrottledTransport {
+    pub fn new(inner: Arc<dyn Transport>, requests_per_second: f64, max_retries: u32) -> NbaResult<ThrottledTransport> {
+        if requests_per_second.is_nan() || requests_per_second <= 0.0 {
+            return Err(NbaError::InvalidParam(format!("requests per second must be positive, got {}", requests_per_second)));
+        }
+        Ok(ThrottledTransport {
+            inner,
+            min_interval: Duration::from_secs_f64(1.0 / requests_per_second),
+            next_request_at: Mutex::new(Instant::now()),
+            max_retries,
+        })
+    }
+
+    /// Blocks until this thread may send the next request.
+    fn wait_for_slot(&self) {
+        let wait = {
+            let mut next_request_at = self.next_request_at.lock().unwrap_or_else(|e| e.into_inner());
+            let now = Instant::now();
+            let slot = (*next_request_at).max(now);
+            *next_request_at = slot + self.min_interval;
+            slot - now
+        };
+        if !wait.is_zero() {
+            thread::sleep(wait);
+        }
+    }
+
+    fn with_retries<T>(&self, url: &str, request: impl Fn() -> NbaResult<T>) -> NbaResult<T> {
+        let throttle = url.starts_with(self.inner.stats_base_url());
+        let mut attempt = 0;
+        loop {
+            if throttle {
+                self.wait_for_slot();
+            }
+            let err = match request() {
+                Ok(res) => return Ok(res),
+                Err(e) => e,
+            };
+            let retry_after = match &err {
+                NbaError::Transport(_) => None,
+                NbaError::HttpStatus { status, .. } if *status >= 500 => None,
+                NbaError::RateLimited { retry_after, .. } => retry_after.map(Duration::from_secs),
+                _ => return Err(err),
+            };
+            if attempt >= self.max_retries {
+                return Err(err);
+            }
+            let delay = backoff_delay(attempt).max(retry_after.unwrap_or(Duration::ZERO));
+            eprintln!("{}, retrying in {:?}", err, delay);
+            thread::sleep(delay);
+            attempt += 1;
+        }
+    }
+}
+
+impl Transport for ThrottledTransport {
+    fn stats_base_url(&self) -> &str {
+        self.inner.stats_base_url()
+    }
+    fn cdn_base_url(&self) -> &str {
+        self.inner.cdn_base_url()
+    }
+    fn get_json(&self, url: &str) -> NbaResult<Value> {
+        self.with_retries(url, || self.inner.get_json(url))
+    }
+    fn get_bytes(&self, url: &str) -> NbaResult<Vec<u8>> {
+        self.with_retries(url, || self.inner.get_bytes(url))
+    }
+    fn get_json_if_modified(&self, url: &str, validators: &Validators) -> NbaResult<Conditional> {
+        self.with_retries(url, || self.inner.get_json_if_modified(url, validators))
+    }
+}
+
+/// `base * 2^attempt`, capped, plus up to 50% random jitter so parallel
+/// workers don't retry in lockstep.
+fn backoff_delay(attempt: u32) -> Duration {
+    let exp_delay = BASE_RETRY_DELAY.saturating_mul(1 << attempt.min(16)).min(MAX_RETRY_DELAY);
+    exp_delay + exp_delay.mul_f64(random_fraction() * 0.5)
+}
+
+// Good enough randomness for jitter without pulling in a rng crate
+fn random_fraction() -> f64 {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or(Duration::ZERO).subsec_nanos() as u64;
+    let mut x = nanos ^ (thread_id_hash() << 32) ^ 0x9E3779B97F4A7C15;
+    x ^= x << 13;
+    x ^= x >> 7;
+    x ^= x << 17;
+    (x % 10_000) as f64 / 10_000.0
+}
+
+fn thread_id_hash() -> u64 {
+    let id = format!("{:?}", thread::current().id());
+    id.bytes().fold(0u64, |hash, b| hash.wrapping_mul(31).wrapping_add(b as u64))
+}
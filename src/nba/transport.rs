@@ -0,0 +1,154 @@
+use std::io::Read;
+use std::time::Duration;
+use crate::nba::error::{NbaError, NbaResult};
+use serde_json::Value;
+
+pub const NBA_STATS_BASE_URL: &str = "https://stats.nba.com/stats";
+pub const NBA_CDN_BASE_URL: &str = "https://cdn.nba.com/static/json";
+
+/// Validators a server sent with a response, sent back on the next request
+/// so an unchanged resource is answered with an empty 304.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+/// Outcome of a conditional request.
+pub enum Conditional {
+    Modified(Value, Validators),
+    NotModified,
+}
+
+/// Everything that goes over the network to nba.com goes through a `Transport`,
+/// endpoints only know paths relative to the stats or cdn base url.
+pub trait Transport: Send + Sync {
+    fn stats_base_url(&self) -> &str;
+    fn cdn_base_url(&self) -> &str;
+    fn get_json(&self, url: &str) -> NbaResult<Value>;
+    fn get_bytes(&self, url: &str) -> NbaResult<Vec<u8>>;
+
+    /// Fetches `url` unless it hasn't changed since the response
+    /// `validators` came with. Transports that can't tell always fetch.
+    fn get_json_if_modified(&self, url: &str, validators: &Validators) -> NbaResult<Conditional> {
+        let _ = validators;
+        Ok(Conditional::Modified(self.get_json(url)?, Validators::default()))
+    }
+
+    /// Fetches `{stats base url}/{endpoint}?{query}`.
+    fn get_stats_json(&self, endpoint: &str, query: &str) -> NbaResult<Value> {
+        let url = format!("{}/{}?{}", self.stats_base_url(), endpoint, query);
+        self.get_json(&url)
+    }
+
+    /// Fetches `{cdn base url}/{path}`.
+    fn get_cdn_json(&self, path: &str) -> NbaResult<Value> {
+        let url = format!("{}/{}", self.cdn_base_url(), path);
+        self.get_json(&url)
+    }
+}
+
+/// Default transport, one shared ureq agent so connections are kept alive
+/// across requests.
+pub struct UreqTransport {
+    agent: ureq::Agent,
+    stats_base_url: String,
+    cdn_base_url: String,
+}
+
+impl UreqTransport {
+    pub fn new(stats_base_url: &str, cdn_base_url: &str, proxy: Option<&str>) -> NbaResult<UreqTransport> {
+        let mut agent_builder = ureq::AgentBuilder::new()
+            .timeout_connect(Duration::from_secs(10))
+            .timeout_read(Duration::from_secs(30));
+        if let Some(proxy_url) = proxy {
+            let proxy = ureq::Proxy::new(proxy_url).map_err(|e| NbaError::InvalidParam(e.to_string()))?;
+            agent_builder = agent_builder.proxy(proxy);
+        }
+        Ok(UreqTransport {
+            agent: agent_builder.build(),
+            stats_base_url: stats_base_url.trim_end_matches('/').to_string(),
+            cdn_base_url: cdn_base_url.trim_end_matches('/').to_string(),
+        })
+    }
+}
+
+impl UreqTransport {
+    fn request(&self, url: &str) -> ureq::Request {
+        // stats.nba.com drops requests that don't look like they came from a browser
+        self.agent.get(url)
+        .set("User-Agent","Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:72.0) Gecko/20100101 Firefox/72.0")
+        .set("Accept","application/json, text/plain, */*")
+        .set("Accept-Language","en-US,en;q=0.5")
+        .set("Accept-Encoding","gzip, deflate, br")
+        .set("Connection","keep-alive")
+        .set("Referer","https://stats.nba.com/")
+    }
+    /// Request that asks caches on the way for a fresh response, which
+    /// stats.nba.com needs. Conditional requests go without it so the
+    /// server can answer 304.
+    fn uncached_request(&self, url: &str) -> ureq::Request {
+        let request = self.request(url);
+        if url.starts_with(&self.stats_base_url) {
+            request.set("Pragma","no-cache").set("Cache-Control","no-cache")
+        } else {
+            request
+        }
+    }
+}
+
+fn read_json(url: &str, r: ureq::Response) -> NbaResult<Value> {
+    let mut body = String::new();
+    r.into_reader().read_to_string(&mut body).map_err(|e| NbaError::Transport(e.to_string()))?;
+    serde_json::from_str(&body).map_err(|e| NbaError::SchemaMismatch(format!("{} did not return json: {}", url, e)))
+}
+
+impl Default for UreqTransport {
+    fn default() -> Self {
+        UreqTransport {
+            agent: ureq::Agent::new(),
+            stats_base_url: NBA_STATS_BASE_URL.to_string(),
+            cdn_base_url: NBA_CDN_BASE_URL.to_string(),
+        }
+    }
+}
+
+impl Transport for UreqTransport {
+    fn stats_base_url(&self) -> &str {
+        &self.stats_base_url
+    }
+    fn cdn_base_url(&self) -> &str {
+        &self.cdn_base_url
+    }
+    fn get_json(&self, url: &str) -> NbaResult<Value> {
+        let r = self.uncached_request(url).call()?;
+        read_json(url, r)
+    }
+    fn get_json_if_modified(&self, url: &str, validators: &Validators) -> NbaResult<Conditional> {
+        let mut request = self.request(url);
+        if let Some(etag) = &validators.etag {
+            request = request.set("If-None-Match", etag);
+        }
+        if let Some(last_modified) = &validators.last_modified {
+            request = request.set("If-Modified-Since", last_modified);
+        }
+        let r = request.call()?;
+        if r.status() == 304 {
+            return Ok(Conditional::NotModified);
+        }
+        let response_validators = Validators {
+            etag: r.header("ETag").map(str::to_string),
+            last_modified: r.header("Last-Modified").map(str::to_string),
+        };
+        Ok(Conditional::Modified(read_json(url, r)?, response_validators))
+    }
+    fn get_bytes(&self, url: &str) -> NbaResult<Vec<u8>> {
+        let r = self.agent.get(url).call()?;
+        let len: usize = r.header("Content-Length")
+            .and_then(|l| l.parse().ok())
+            .unwrap_or(0);
+        let mut bytes: Vec<u8> = Vec::with_capacity(len);
+        r.into_reader().read_to_end(&mut bytes).map_err(|e| NbaError::Transport(e.to_string()))?;
+        Ok(bytes)
+    }
+}
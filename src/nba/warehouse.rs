@@ -0,0 +1,777 @@
+use crate::nba::db::{inferred_create_statement, inferred_table_name, quote_identifier, row_values};
+use crate::nba::error::{NbaError, NbaResult};
+
+use std::{collections::{BTreeMap, BTreeSet}, path::Path, rc::Rc, time::Duration};
+use rusqlite::{params, Connection};
+use serde_json::Value;
+
+pub const DEFAULT_WAREHOUSE_PATH: &str = "nba_warehouse.db";
+
+/// Version of the layout `TABLES` describes, a fresh warehouse starts here.
+pub const SCHEMA_VERSION: i64 = 7;
+
+/// Forward migration from `version - 1` to `version`. A fresh warehouse is
+/// created from `TABLES` directly, migrations only run on older files.
+struct Migration {
+    version: i64,
+    description: &'static str,
+    apply: fn(&Connection) -> NbaResult<()>,
+}
+
+// Append only, never edit a migration that has shipped
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 2,
+        description: "record when and from where each table was fetched",
+        apply: |conn| Ok(conn.execute_batch(FETCH_METADATA_TABLE)?),
+    },
+    Migration {
+        version: 3,
+        description: "fold the per game play by play tables into playbyplay",
+        apply: fold_playbyplay_tables,
+    },
+    Migration {
+        version: 4,
+        description: "add the sync checkpoint and the declared game summary tables",
+        apply: add_sync_tables,
+    },
+    Migration {
+        version: 5,
+        description: "record the alerts fired for each game",
+        apply: |conn| Ok(conn.execute_batch(FIRED_ALERTS_TABLE)?),
+    },
+    Migration {
+        version: 6,
+        description: "name inferred tables after their endpoint",
+        apply: namespace_inferred_tables,
+    },
+    Migration {
+        version: LEGACY_FOLD_VERSION,
+        description: "fold playbyplay.db, nba_siren.db and all_players.db into the warehouse",
+        apply: fold_legacy_databases,
+    },
+];
+
+const LEGACY_FOLD_VERSION: i64 = 7;
+
+// Files written before the warehouse, looked for next to it, the schema each
+// is attached as and the endpoint its result sets came from
+const LEGACY_DATABASES: &[(&str, &str, &str)] = &[
+    ("playbyplay.db", "legacy_playbyplay", "playbyplayv2"),
+    ("nba_siren.db", "legacy_siren", "boxscoreplayertrackv2"),
+    ("all_players.db", "legacy_players", "commonallplayers"),
+];
+
+const FETCH_METADATA_TABLE: &str = "CREATE TABLE IF NOT EXISTS fetch_metadata (
+    url TEXT NOT NULL,
+    table_name TEXT NOT NULL,
+    fetched_at TEXT NOT NULL,
+    row_count INTEGER NOT NULL,
+    PRIMARY KEY (url, table_name)
+)";
+
+/// Alerts delivered by `siren`, a rule fires at most once per game.
+const FIRED_ALERTS_TABLE: &str = "CREATE TABLE IF NOT EXISTS fired_alerts (
+    rule TEXT NOT NULL,
+    game_id TEXT NOT NULL,
+    message TEXT NOT NULL,
+    fired_at TEXT NOT NULL,
+    PRIMARY KEY (rule, game_id)
+)";
+
+/// Steps of `sync season` done for each game, so an interrupted run can
+/// pick up where it stopped.
+const SYNC_CHECKPOINT_TABLE: &str = "CREATE TABLE IF NOT EXISTS sync_checkpoint (
+    game_id TEXT NOT NULL,
+    step TEXT NOT NULL,
+    season TEXT NOT NULL,
+    season_type TEXT NOT NULL,
+    completed_at TEXT NOT NULL,
+    PRIMARY KEY (game_id, step)
+)";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SqlType {
+    Integer,
+    Real,
+    Text,
+}
+
+impl SqlType {
+    pub fn sql(&self) -> &'static str {
+        match self {
+            SqlType::Integer => "INTEGER",
+            SqlType::Real => "REAL",
+            SqlType::Text => "TEXT",
+        }
+    }
+
+    /// Type of a column declared as `declared`, `None` for an untyped column.
+    /// Tables made before REAL was used have FLOAT columns.
+    pub fn from_declared(declared: &str) -> Option<SqlType> {
+        match declared.to_uppercase().as_str() {
+            "INTEGER" => Some(SqlType::Integer),
+            "REAL" | "FLOAT" => Some(SqlType::Real),
+            "TEXT" => Some(SqlType::Text),
+            _ => None,
+        }
+    }
+
+    /// Type a json value is stored as, `None` for null.
+    pub fn of(val: &Value) -> Option<SqlType> {
+        match val {
+            Value::Null => None,
+            Value::Bool(_) => Some(SqlType::Integer),
+            Value::Number(n) if n.is_f64() => Some(SqlType::Real),
+            Value::Number(_) => Some(SqlType::Integer),
+            _ => Some(SqlType::Text),
+        }
+    }
+
+    /// Whether a column of this type can hold `val`.
+    pub fn accepts(&self, val: &Value) -> bool {
+        match (self, SqlType::of(val)) {
+            (_, None) | (SqlType::Real, Some(SqlType::Integer)) => true,
+            (column_type, Some(val_type)) => *column_type == val_type,
+        }
+    }
+}
+
+/// Column types of a result set, scanning every row. A column holding
+/// integers and floats is REAL, one that is null in every row gets no type.
+/// Fails on a column holding both numbers and strings.
+pub fn infer_column_types(table_name: &str, column_names: &[String], rows: &[Value]) -> NbaResult<Vec<Option<SqlType>>> {
+    let mut column_types: Vec<Option<SqlType>> = vec![None; column_names.len()];
+    for (pos, row) in rows.iter().enumerate() {
+        for (col, val) in row_values(table_name, pos, row, column_names.len())?.iter().enumerate() {
+            column_types[col] = match (column_types[col], SqlType::of(val)) {
+                (column_type, None) => column_type,
+                (None, val_type) => val_type,
+                (Some(SqlType::Integer), Some(SqlType::Real)) => Some(SqlType::Real),
+                (Some(column_type), Some(_)) if column_type.accepts(val) => Some(column_type),
+                (Some(column_type), Some(_)) => return Err(type_mismatch(table_name, pos, &column_names[col], column_type, val)),
+            };
+        }
+    }
+    Ok(column_types)
+}
+
+/// Fails on the first value its column's type can't hold, untyped columns
+/// hold anything.
+pub fn check_column_types(table_name: &str, column_names: &[String], column_types: &[Option<SqlType>], rows: &[Value]) -> NbaResult<()> {
+    for (pos, row) in rows.iter().enumerate() {
+        for (col, val) in row_values(table_name, pos, row, column_names.len())?.iter().enumerate() {
+            if let Some(column_type) = column_types[col] {
+                if !column_type.accepts(val) {
+                    return Err(type_mismatch(table_name, pos, &column_names[col], column_type, val));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn type_mismatch(table_name: &str, pos: usize, column_name: &str, column_type: SqlType, val: &Value) -> NbaError {
+    NbaError::SchemaMismatch(format!("{} row {} has {} in {} column {}", table_name, pos, val, column_type.sql(), column_name))
+}
+
+/// Types of `column_names` in the existing table `table_name`, `None` if
+/// there's no such table. Fails on a column the table doesn't have.
+pub fn existing_column_types(conn: &Connection, table_name: &str, column_names: &[String]) -> NbaResult<Option<Vec<Option<SqlType>>>> {
+    let declared_columns = table_columns(conn, table_name)?;
+    if declared_columns.is_empty() {
+        return Ok(None);
+    }
+    let mut column_types = Vec::with_capacity(column_names.len());
+    for column_name in column_names {
+        let (_, declared) = declared_columns.iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(column_name))
+            .ok_or_else(|| NbaError::SchemaMismatch(format!("{} has no column {}", table_name, column_name)))?;
+        column_types.push(*declared);
+    }
+    Ok(Some(column_types))
+}
+
+/// Columns of `table_name` in table order with their declared types, none
+/// when the table doesn't exist.
+pub fn table_columns(conn: &Connection, table_name: &str) -> NbaResult<Vec<(String, Option<SqlType>)>> {
+    let mut table_info = conn.prepare(&format!("PRAGMA table_info({})", quote_identifier(table_name)))?;
+    let declared_columns = table_info.query_map([], |r| Ok((r.get::<_, String>(1)?, r.get::<_, String>(2)?)))?
+        .collect::<rusqlite::Result<Vec<(String, String)>>>()?;
+    Ok(declared_columns.into_iter()
+        .map(|(name, declared)| (name, SqlType::from_declared(&declared)))
+        .collect())
+}
+
+/// Declared layout of a warehouse table. Every column is nullable, `key`
+/// is the natural key a row is unique on.
+pub struct TableSchema {
+    pub name: &'static str,
+    pub columns: &'static [(&'static str, SqlType)],
+    pub key: &'static [&'static str],
+    pub indexes: &'static [&'static str],
+}
+
+impl TableSchema {
+    /// `CREATE TABLE` for this layout, under another name when the same
+    /// layout is used by several tables.
+    pub fn create_statement(&self, table_name: &str) -> String {
+        let mut column_defs = vec!["id INTEGER PRIMARY KEY".to_string()];
+        column_defs.extend(self.columns.iter().map(|(name, sql_type)| format!("{} {}", quote_identifier(name), sql_type.sql())));
+        if !self.key.is_empty() {
+            let key: Vec<String> = self.key.iter().map(|k| quote_identifier(k)).collect();
+            column_defs.push(format!("UNIQUE ({})", key.join(", ")));
+        }
+        format!("CREATE TABLE IF NOT EXISTS {} ({})", quote_identifier(table_name), column_defs.join(", "))
+    }
+
+    /// Creates the table and its indexes if they don't exist yet.
+    pub fn create(&self, conn: &Connection, table_name: &str) -> NbaResult<()> {
+        conn.execute(&self.create_statement(table_name), [])?;
+        for column in self.indexes {
+            conn.execute(&format!(
+                "CREATE INDEX IF NOT EXISTS {} ON {} ({})",
+                quote_identifier(&format!("{}_{}", table_name, column)),
+                quote_identifier(table_name),
+                quote_identifier(column),
+            ), [])?;
+        }
+        Ok(())
+    }
+
+    pub fn column_type(&self, column: &str) -> Option<SqlType> {
+        self.columns.iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(column))
+            .map(|(_, sql_type)| *sql_type)
+    }
+
+    pub fn has_column(&self, column: &str) -> bool {
+        self.column_type(column).is_some()
+    }
+}
+
+use SqlType::{Integer, Real, Text};
+
+pub const COMMONALLPLAYERS: TableSchema = TableSchema {
+    name: "commonallplayers",
+    columns: &[
+        ("person_id", Integer), ("display_last_comma_first", Text), ("display_first_last", Text),
+        ("rosterstatus", Integer), ("from_year", Text), ("to_year", Text), ("playercode", Text),
+        ("player_slug", Text), ("team_id", Integer), ("team_city", Text), ("team_name", Text),
+        ("team_abbreviation", Text), ("team_code", Text), ("team_slug", Text),
+        ("games_played_flag", Text), ("otherleague_experience_ch", Text),
+    ],
+    key: &["person_id"],
+    indexes: &[],
+};
+
+pub const PLAYERSTATS: TableSchema = TableSchema {
+    name: "playerstats",
+    columns: &[
+        ("game_id", Text), ("team_id", Integer), ("team_abbreviation", Text), ("team_city", Text),
+        ("player_id", Integer), ("player_name", Text), ("start_position", Text), ("comment", Text),
+        ("min", Text), ("spd", Real), ("dist", Real), ("orbc", Integer), ("drbc", Integer),
+        ("rbc", Integer), ("tchs", Integer), ("sast", Integer), ("ftast", Integer), ("pass", Integer),
+        ("ast", Integer), ("cfgm", Integer), ("cfga", Integer), ("cfg_pct", Real), ("ufgm", Integer),
+        ("ufga", Integer), ("ufg_pct", Real), ("fg_pct", Real), ("dfgm", Integer), ("dfga", Integer),
+        ("dfg_pct", Real),
+    ],
+    key: &["game_id", "player_id"],
+    indexes: &["player_id"],
+};
+
+pub const TEAMSTATS: TableSchema = TableSchema {
+    name: "teamstats",
+    columns: &[
+        ("game_id", Text), ("team_id", Integer), ("team_name", Text), ("team_abbreviation", Text),
+        ("team_city", Text), ("min", Text), ("dist", Real), ("orbc", Integer), ("drbc", Integer),
+        ("rbc", Integer), ("tchs", Integer), ("sast", Integer), ("ftast", Integer), ("pass", Integer),
+        ("ast", Integer), ("cfgm", Integer), ("cfga", Integer), ("cfg_pct", Real), ("ufgm", Integer),
+        ("ufga", Integer), ("ufg_pct", Real), ("fg_pct", Real), ("dfgm", Integer), ("dfga", Integer),
+        ("dfg_pct", Real),
+    ],
+    key: &["game_id", "team_id"],
+    indexes: &["team_id"],
+};
+
+/// Every event of every game stored, one row per game id and event number.
+pub const PLAYBYPLAY: TableSchema = TableSchema {
+    name: "playbyplay",
+    columns: &[
+        ("game_id", Text), ("eventnum", Integer), ("eventmsgtype", Integer), ("eventmsgactiontype", Integer),
+        ("period", Integer), ("wctimestring", Text), ("pctimestring", Text), ("homedescription", Text),
+        ("neutraldescription", Text), ("visitordescription", Text), ("score", Text), ("scoremargin", Text),
+        ("person1type", Integer), ("player1_id", Integer), ("player1_name", Text), ("player1_team_id", Integer),
+        ("player1_team_city", Text), ("player1_team_nickname", Text), ("player1_team_abbreviation", Text),
+        ("person2type", Integer), ("player2_id", Integer), ("player2_name", Text), ("player2_team_id", Integer),
+        ("player2_team_city", Text), ("player2_team_nickname", Text), ("player2_team_abbreviation", Text),
+        ("person3type", Integer), ("player3_id", Integer), ("player3_name", Text), ("player3_team_id", Integer),
+        ("player3_team_city", Text), ("player3_team_nickname", Text), ("player3_team_abbreviation", Text),
+        ("video_available_flag", Integer),
+    ],
+    key: &["game_id", "eventnum"],
+    indexes: &[
+        "player1_id", "player2_id", "player3_id",
+        "player1_team_id", "player2_team_id", "player3_team_id",
+        "period", "eventmsgtype",
+    ],
+};
+
+pub const GAMESUMMARY: TableSchema = TableSchema {
+    name: "gamesummary",
+    columns: &[
+        ("game_date_est", Text), ("game_sequence", Integer), ("game_id", Text), ("game_status_id", Integer),
+        ("game_status_text", Text), ("gamecode", Text), ("home_team_id", Integer), ("visitor_team_id", Integer),
+        ("season", Text), ("live_period", Integer), ("live_pc_time", Text), ("natl_tv_broadcaster_abbreviation", Text),
+        ("live_period_time_bcast", Text), ("wh_status", Integer),
+    ],
+    key: &["game_id"],
+    indexes: &[],
+};
+
+/// Points per period of each team of a game.
+pub const LINESCORE: TableSchema = TableSchema {
+    name: "linescore",
+    columns: &[
+        ("game_date_est", Text), ("game_sequence", Integer), ("game_id", Text), ("team_id", Integer),
+        ("team_abbreviation", Text), ("team_city_name", Text), ("team_nickname", Text), ("team_wins_losses", Text),
+        ("pts_qtr1", Integer), ("pts_qtr2", Integer), ("pts_qtr3", Integer), ("pts_qtr4", Integer),
+        ("pts_ot1", Integer), ("pts_ot2", Integer), ("pts_ot3", Integer), ("pts_ot4", Integer), ("pts_ot5", Integer),
+        ("pts_ot6", Integer), ("pts_ot7", Integer), ("pts_ot8", Integer), ("pts_ot9", Integer), ("pts_ot10", Integer),
+        ("pts", Integer),
+    ],
+    key: &["game_id", "team_id"],
+    indexes: &["team_id"],
+};
+
+/// Tables every warehouse has.
+const TABLES: &[&TableSchema] = &[&COMMONALLPLAYERS, &PLAYERSTATS, &TEAMSTATS, &PLAYBYPLAY, &GAMESUMMARY, &LINESCORE];
+
+/// Column rows of result sets without a registered table are stamped with,
+/// the url of the request they came from.
+pub const REQUEST_URL_COLUMN: &str = "request_url";
+
+/// Which table each result set of an endpoint is stored in. Result sets not
+/// listed get a table inferred from their rows.
+const REGISTRY: &[(&str, &str, &TableSchema)] = &[
+    ("commonallplayers", "CommonAllPlayers", &COMMONALLPLAYERS),
+    ("boxscoreplayertrackv2", "PlayerStats", &PLAYERSTATS),
+    ("boxscoreplayertrackv2", "TeamStats", &TEAMSTATS),
+    ("playbyplayv2", "PlayByPlay", &PLAYBYPLAY),
+    ("boxscoresummaryv2", "GameSummary", &GAMESUMMARY),
+    ("boxscoresummaryv2", "LineScore", &LINESCORE),
+];
+
+pub fn registered_table(endpoint: &str, result_set: &str) -> Option<&'static TableSchema> {
+    REGISTRY.iter()
+        .find(|(e, r, _)| e.eq_ignore_ascii_case(endpoint) && r.eq_ignore_ascii_case(result_set))
+        .map(|(_, _, schema)| *schema)
+}
+
+/// The local sqlite database everything fetched is stored in, shared by
+/// every endpoint of a run.
+#[derive(Clone)]
+pub struct Warehouse {
+    connection: Rc<Connection>,
+    max_age: Option<Duration>,
+}
+
+impl Warehouse {
+    /// Opens or creates the warehouse at `path` and brings it up to
+    /// `SCHEMA_VERSION`. Data fetched less than `max_age` ago isn't fetched
+    /// again, without a `max_age` everything is.
+    pub fn open(path: &str, max_age: Option<Duration>) -> NbaResult<Warehouse> {
+        let connection = Connection::open(path)?;
+        migrate(&connection, path)?;
+        Ok(Warehouse { connection: Rc::new(connection), max_age })
+    }
+
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
+    /// Whether what `url` returned was stored recently enough to skip it.
+    pub fn is_fresh(&self, url: &str) -> NbaResult<bool> {
+        let max_age = match self.max_age {
+            Some(max_age) => max_age,
+            None => return Ok(false),
+        };
+        let fresh: bool = self.connection.query_row(
+            "SELECT EXISTS (SELECT 1 FROM fetch_metadata WHERE url = ?1 AND fetched_at >= datetime('now', ?2))",
+            params![url, format!("-{} seconds", max_age.as_secs())],
+            |r| r.get(0),
+        )?;
+        Ok(fresh)
+    }
+
+    /// Notes that `table_name` was just filled with `row_count` rows from `url`.
+    pub fn record_fetch(&self, url: &str, table_name: &str, row_count: usize) -> NbaResult<()> {
+        self.connection.execute(
+            "INSERT INTO fetch_metadata (url, table_name, fetched_at, row_count) VALUES (?1, ?2, datetime('now'), ?3)
+            ON CONFLICT (url, table_name) DO UPDATE SET fetched_at = excluded.fetched_at, row_count = excluded.row_count",
+            params![url, table_name, row_count as i64],
+        )?;
+        Ok(())
+    }
+
+    /// Whether `sync season` already did `step` for `game_id`.
+    pub fn is_synced(&self, game_id: &str, step: &str) -> NbaResult<bool> {
+        let synced: bool = self.connection.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sync_checkpoint WHERE game_id = ?1 AND step = ?2)",
+            params![game_id, step],
+            |r| r.get(0),
+        )?;
+        Ok(synced)
+    }
+
+    /// Notes that `step` of `game_id` of a season was just synced.
+    pub fn record_sync(&self, season: &str, season_type: &str, game_id: &str, step: &str) -> NbaResult<()> {
+        self.connection.execute(
+            "INSERT INTO sync_checkpoint (game_id, step, season, season_type, completed_at) VALUES (?1, ?2, ?3, ?4, datetime('now'))
+            ON CONFLICT (game_id, step) DO UPDATE SET completed_at = excluded.completed_at",
+            params![game_id, step, season, season_type],
+        )?;
+        Ok(())
+    }
+
+    /// Whether `rule` already fired for `game_id`.
+    pub fn has_fired(&self, rule: &str, game_id: &str) -> NbaResult<bool> {
+        let fired: bool = self.connection.query_row(
+            "SELECT EXISTS (SELECT 1 FROM fired_alerts WHERE rule = ?1 AND game_id = ?2)",
+            params![rule, game_id],
+            |r| r.get(0),
+        )?;
+        Ok(fired)
+    }
+
+    /// Notes that `rule` fired for `game_id`, so it won't again.
+    pub fn record_alert(&self, rule: &str, game_id: &str, message: &str) -> NbaResult<()> {
+        self.connection.execute(
+            "INSERT OR IGNORE INTO fired_alerts (rule, game_id, message, fired_at) VALUES (?1, ?2, ?3, datetime('now'))",
+            params![rule, game_id, message],
+        )?;
+        Ok(())
+    }
+}
+
+fn migrate(conn: &Connection, path: &str) -> NbaResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS schema_version (version INTEGER PRIMARY KEY, description TEXT NOT NULL, applied_at TEXT NOT NULL)",
+        [],
+    )?;
+    let current_version: i64 = conn.query_row("SELECT COALESCE(MAX(version), 0) FROM schema_version", [], |r| r.get(0))?;
+    // Databases can't be attached inside the transactions below
+    let legacy_schemas = if current_version < LEGACY_FOLD_VERSION {
+        attach_legacy_databases(conn, path)?
+    } else {
+        Vec::new()
+    };
+    let migrated = run_migrations(conn, current_version);
+    for schema in legacy_schemas {
+        conn.execute(&format!("DETACH DATABASE {}", quote_identifier(schema)), [])?;
+    }
+    migrated
+}
+
+fn run_migrations(conn: &Connection, current_version: i64) -> NbaResult<()> {
+    if current_version == 0 {
+        let tx = conn.unchecked_transaction()?;
+        for table in TABLES {
+            table.create(&tx, table.name)?;
+        }
+        tx.execute(FETCH_METADATA_TABLE, [])?;
+        tx.execute(SYNC_CHECKPOINT_TABLE, [])?;
+        tx.execute(FIRED_ALERTS_TABLE, [])?;
+        fold_legacy_databases(&tx)?;
+        record_version(&tx, SCHEMA_VERSION, "create warehouse")?;
+        return Ok(tx.commit()?);
+    }
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current_version) {
+        let tx = conn.unchecked_transaction()?;
+        (migration.apply)(&tx)?;
+        record_version(&tx, migration.version, migration.description)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+fn record_version(conn: &Connection, version: i64, description: &str) -> NbaResult<()> {
+    conn.execute(
+        "INSERT INTO schema_version (version, description, applied_at) VALUES (?1, ?2, datetime('now'))",
+        params![version, description],
+    )?;
+    Ok(())
+}
+
+// Before version 3 every game had its own `playbyplay_{game_id}` table
+fn fold_playbyplay_tables(conn: &Connection) -> NbaResult<()> {
+    PLAYBYPLAY.create(conn, PLAYBYPLAY.name)?;
+    let game_tables = conn.prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name LIKE 'playbyplay\\_%' ESCAPE '\\'")?
+        .query_map([], |r| r.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+    for game_table in game_tables {
+        let game_columns = conn.prepare(&format!("SELECT * FROM {} LIMIT 0", quote_identifier(&game_table)))?
+            .column_names()
+            .iter()
+            .map(|col| col.to_lowercase())
+            .filter(|col| PLAYBYPLAY.has_column(col))
+            .map(|col| quote_identifier(&col))
+            .collect::<Vec<String>>()
+            .join(", ");
+        conn.execute(&format!(
+            "INSERT OR IGNORE INTO {} ({cols}) SELECT {cols} FROM {}",
+            quote_identifier(PLAYBYPLAY.name), quote_identifier(&game_table), cols = game_columns,
+        ), [])?;
+        conn.execute(&format!("DROP TABLE {}", quote_identifier(&game_table)), [])?;
+        conn.execute("UPDATE fetch_metadata SET table_name = ?1 WHERE table_name = ?2", params![PLAYBYPLAY.name, game_table])?;
+    }
+    Ok(())
+}
+
+// Before version 4 game summaries, like any unregistered result set, went
+// to tables inferred from their rows. Those are rebuilt with the declared
+// layout so rows can be matched on their key.
+fn add_sync_tables(conn: &Connection) -> NbaResult<()> {
+    conn.execute_batch(SYNC_CHECKPOINT_TABLE)?;
+    for schema in [&GAMESUMMARY, &LINESCORE] {
+        let inferred_columns = table_columns(conn, schema.name)?;
+        if inferred_columns.is_empty() {
+            schema.create(conn, schema.name)?;
+            continue;
+        }
+        let inferred_table = format!("{}_inferred", schema.name);
+        conn.execute(&format!("ALTER TABLE {} RENAME TO {}", quote_identifier(schema.name), quote_identifier(&inferred_table)), [])?;
+        // Indexes keep their names through the rename, drop them so the declared ones can be created
+        conn.execute(&format!("DROP INDEX IF EXISTS {}", quote_identifier(&format!("{}_key", schema.name))), [])?;
+        schema.create(conn, schema.name)?;
+        let columns = inferred_columns.iter()
+            .map(|(name, _)| name.to_lowercase())
+            .filter(|name| schema.has_column(name))
+            .map(|name| quote_identifier(&name))
+            .collect::<Vec<String>>()
+            .join(", ");
+        conn.execute(&format!(
+            "INSERT OR IGNORE INTO {} ({cols}) SELECT {cols} FROM {} ORDER BY id DESC",
+            quote_identifier(schema.name), quote_identifier(&inferred_table), cols = columns,
+        ), [])?;
+        conn.execute(&format!("DROP TABLE {}", quote_identifier(&inferred_table)), [])?;
+    }
+    Ok(())
+}
+
+// Before version 6 a result set without a registered table went to a table
+// named after the result set alone, which could be a declared table's name.
+// Tables `fetch_metadata` has from a single endpoint are renamed after it,
+// the others are left as they are.
+fn namespace_inferred_tables(conn: &Connection) -> NbaResult<()> {
+    let fetches = conn.prepare("SELECT table_name, url FROM fetch_metadata")?
+        .query_map([], |r| Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?)))?
+        .collect::<rusqlite::Result<Vec<(String, String)>>>()?;
+    let mut table_endpoints: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    for (table_name, url) in fetches {
+        if TABLES.iter().any(|table| table.name == table_name) {
+            continue;
+        }
+        let path = url.split('?').next().unwrap_or_default();
+        let endpoint = path.rsplit('/').next().unwrap_or_default().to_lowercase();
+        table_endpoints.entry(table_name).or_default().insert(endpoint);
+    }
+    for (table_name, endpoints) in table_endpoints {
+        let endpoint = match endpoints.iter().next() {
+            Some(endpoint) if endpoints.len() == 1 => endpoint,
+            _ => continue,
+        };
+        let namespaced = inferred_table_name(endpoint, &table_name);
+        let columns: Vec<String> = table_columns(conn, &table_name)?.into_iter().map(|(name, _)| name).collect();
+        if namespaced == table_name || columns.is_empty() || !table_columns(conn, &namespaced)?.is_empty() {
+            continue;
+        }
+        conn.execute(&format!("ALTER TABLE {} RENAME TO {}", quote_identifier(&table_name), quote_identifier(&namespaced)), [])?;
+        conn.execute("UPDATE fetch_metadata SET table_name = ?1 WHERE table_name = ?2", params![namespaced, table_name])?;
+    }
+    Ok(())
+}
+
+fn attach_legacy_databases(conn: &Connection, warehouse_path: &str) -> NbaResult<Vec<&'static str>> {
+    // An in-memory warehouse has nothing next to it
+    if warehouse_path.is_empty() || warehouse_path == ":memory:" {
+        return Ok(Vec::new());
+    }
+    let directory = Path::new(warehouse_path).parent().unwrap_or_else(|| Path::new(""));
+    let mut attached = Vec::new();
+    for (file, schema, _) in LEGACY_DATABASES {
+        let legacy_path = directory.join(file);
+        if legacy_path.is_file() {
+            conn.execute(&format!("ATTACH DATABASE ?1 AS {}", quote_identifier(schema)), params![legacy_path.to_string_lossy()])?;
+            attached.push(*schema);
+        }
+    }
+    Ok(attached)
+}
+
+// Before version 7 play by play went to `playbyplay.db`, in a table per
+// game, box scores to `nba_siren.db` and the player list to
+// `all_players.db`. Whichever of them `migrate` found next to the warehouse
+// and attached is copied into the tables the same result sets are stored
+// in now. The files are left as they are.
+fn fold_legacy_databases(conn: &Connection) -> NbaResult<()> {
+    let attached = conn.prepare("PRAGMA database_list")?
+        .query_map([], |r| r.get::<_, String>(1))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+    for (_, schema, endpoint) in LEGACY_DATABASES.iter().filter(|(_, schema, _)| attached.iter().any(|a| a == schema)) {
+        let legacy_tables = conn.prepare(&format!("SELECT name FROM {}.sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite\\_%' ESCAPE '\\'", quote_identifier(schema)))?
+            .query_map([], |r| r.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        for legacy_table in legacy_tables {
+            let result_set = legacy_table.to_lowercase();
+            let source = format!("{}.{}", quote_identifier(schema), quote_identifier(&legacy_table));
+            let target = if result_set.starts_with("playbyplay_") {
+                PLAYBYPLAY.name.to_string()
+            } else {
+                registered_table(endpoint, &result_set)
+                    .map_or_else(|| inferred_table_name(endpoint, &result_set), |table| table.name.to_string())
+            };
+            // The legacy row ids mean nothing here and would collide with stored rows
+            let legacy_columns = conn.prepare(&format!("PRAGMA {}.table_info({})", quote_identifier(schema), quote_identifier(&legacy_table)))?
+                .query_map([], |r| Ok((r.get::<_, String>(1)?.to_lowercase(), SqlType::from_declared(&r.get::<_, String>(2)?))))?
+                .collect::<rusqlite::Result<Vec<(String, Option<SqlType>)>>>()?
+                .into_iter()
+                .filter(|(name, _)| name != "id")
+                .collect::<Vec<_>>();
+            let mut target_columns = table_columns(conn, &target)?;
+            if target_columns.is_empty() {
+                // Laid out like `create_table` lays out a result set it infers
+                let (names, types): (Vec<String>, Vec<Option<SqlType>>) = legacy_columns.iter().cloned().unzip();
+                conn.execute(&inferred_create_statement(&target, &names, &types), [])?;
+                target_columns = table_columns(conn, &target)?;
+            }
+            let columns = legacy_columns.iter()
+                .map(|(name, _)| name)
+                .filter(|col| target_columns.iter().any(|(name, _)| name.eq_ignore_ascii_case(col)))
+                .map(|col| quote_identifier(col))
+                .collect::<Vec<String>>()
+                .join(", ");
+            if columns.is_empty() {
+                continue;
+            }
+            conn.execute(&format!(
+                "INSERT OR IGNORE INTO main.{} ({cols}) SELECT {cols} FROM {}",
+                quote_identifier(&target), source, cols = columns,
+            ), [])?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, path::PathBuf, process};
+
+    // An empty directory of its own for each test
+    fn test_directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("nbasiren-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    // The files older versions wrote, laid out the way they wrote them
+    fn write_legacy_databases(directory: &Path) {
+        Connection::open(directory.join("playbyplay.db")).unwrap().execute_batch(
+            "CREATE TABLE playbyplay_0042100401 (id integer primary key, game_id TEXT, eventnum INTEGER, eventmsgtype INTEGER, period INTEGER, pctimestring TEXT, homedescription TEXT);
+            INSERT INTO playbyplay_0042100401 VALUES (1, '0042100401', 1, 12, 1, '12:00', NULL), (2, '0042100401', 4, 1, 1, '11:40', 'Curry 26'' 3PT Jump Shot (3 PTS)');
+            CREATE TABLE availablevideo (id integer primary key, video_available_flag INTEGER);
+            INSERT INTO availablevideo VALUES (1, 1);",
+        ).unwrap();
+        Connection::open(directory.join("nba_siren.db")).unwrap().execute_batch(
+            "CREATE TABLE playerstats (id integer primary key, game_id TEXT, player_id INTEGER, player_name TEXT, pass INTEGER, dist FLOAT);
+            INSERT INTO playerstats VALUES (1, '0042100401', 201939, 'Stephen Curry', 52, 2.5);",
+        ).unwrap();
+        Connection::open(directory.join("all_players.db")).unwrap().execute_batch(
+            "CREATE TABLE commonallplayers (id integer primary key, person_id INTEGER, display_first_last TEXT);
+            INSERT INTO commonallplayers VALUES (1, 201939, 'Stephen Curry');",
+        ).unwrap();
+    }
+
+    fn count(warehouse: &Warehouse, table_name: &str) -> i64 {
+        warehouse.connection()
+            .query_row(&format!("SELECT COUNT(*) FROM {}", quote_identifier(table_name)), [], |r| r.get(0))
+            .unwrap()
+    }
+
+    fn schema_version(warehouse: &Warehouse) -> i64 {
+        warehouse.connection().query_row("SELECT MAX(version) FROM schema_version", [], |r| r.get(0)).unwrap()
+    }
+
+    #[test]
+    fn new_warehouse_folds_legacy_files_next_to_it() {
+        let directory = test_directory("fold-new");
+        write_legacy_databases(&directory);
+        let warehouse = Warehouse::open(directory.join("warehouse.db").to_str().unwrap(), None).unwrap();
+        assert_eq!(count(&warehouse, "playbyplay"), 2);
+        assert_eq!(count(&warehouse, "playerstats"), 1);
+        assert_eq!(count(&warehouse, "commonallplayers"), 1);
+        assert_eq!(count(&warehouse, "playbyplayv2_availablevideo"), 1);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn legacy_rows_get_new_ids_and_the_current_layout() {
+        let directory = test_directory("fold-layout");
+        let path = directory.join("warehouse.db");
+        let warehouse = Warehouse::open(path.to_str().unwrap(), None).unwrap();
+        warehouse.connection().execute(
+            "INSERT INTO playbyplay (id, game_id, eventnum, period) VALUES (1, '0042100402', 1, 1), (2, '0042100402', 2, 1)", [],
+        ).unwrap();
+        warehouse.connection().execute("UPDATE schema_version SET version = 6", []).unwrap();
+        drop(warehouse);
+
+        write_legacy_databases(&directory);
+        let warehouse = Warehouse::open(path.to_str().unwrap(), None).unwrap();
+        // Rows stored before keep theirs, legacy rows with the same ids are added
+        assert_eq!(count(&warehouse, "playbyplay"), 4);
+        // A table only the legacy files had is laid out like an inferred one
+        let video_columns = warehouse.connection().prepare("PRAGMA table_info(playbyplayv2_availablevideo)").unwrap()
+            .query_map([], |r| Ok((r.get::<_, String>(1)?, r.get::<_, String>(2)?, r.get::<_, i64>(5)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<Vec<(String, String, i64)>>>()
+            .unwrap();
+        assert_eq!(video_columns, vec![
+            ("id".to_string(), "INTEGER".to_string(), 1),
+            ("video_available_flag".to_string(), "INTEGER".to_string(), 0),
+        ]);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn legacy_files_elsewhere_are_left_alone() {
+        let legacy_directory = test_directory("fold-elsewhere-legacy");
+        write_legacy_databases(&legacy_directory);
+        let directory = test_directory("fold-elsewhere");
+        let warehouse = Warehouse::open(directory.join("warehouse.db").to_str().unwrap(), None).unwrap();
+        assert_eq!(count(&warehouse, "playbyplay"), 0);
+        fs::remove_dir_all(&legacy_directory).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn migration_folds_legacy_files_into_an_older_warehouse() {
+        let directory = test_directory("fold-migrate");
+        let path = directory.join("warehouse.db");
+        let warehouse = Warehouse::open(path.to_str().unwrap(), None).unwrap();
+        warehouse.connection().execute("UPDATE schema_version SET version = 6", []).unwrap();
+        drop(warehouse);
+
+        write_legacy_databases(&directory);
+        let warehouse = Warehouse::open(path.to_str().unwrap(), None).unwrap();
+        assert_eq!(schema_version(&warehouse), SCHEMA_VERSION);
+        assert_eq!(count(&warehouse, "playbyplay"), 2);
+        assert_eq!(count(&warehouse, "playerstats"), 1);
+        // Folded once, opening it again doesn't copy anything twice
+        drop(warehouse);
+        let warehouse = Warehouse::open(path.to_str().unwrap(), None).unwrap();
+        assert_eq!(count(&warehouse, "playbyplayv2_availablevideo"), 1);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
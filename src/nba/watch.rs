@@ -0,0 +1,234 @@
+use crate::nba::error::{NbaError, NbaResult};
+use crate::nba::events::PlayByPlayEvent;
+use crate::nba::filter::EventFilter;
+use crate::nba::live_data::{
+    boxscore_from_json, boxscore_game_status, boxscore_url, live_events_from_json, live_game_ended, live_scoreboard_from_json,
+    period_label, playbyplay_url, scoreboard_snapshots, scoreboard_url, GameSnapshot,
+};
+use crate::nba::output::{Output, OutputTable};
+use crate::nba::transport::{Conditional, Transport, Validators};
+
+use serde_json::Value;
+use std::{cmp::Ordering, collections::HashMap, thread, time::Duration};
+
+pub const DEFAULT_WATCH_INTERVAL: &str = "15s";
+
+/// A feed polled over and over. The validators of the last response are
+/// sent back with each poll so an unchanged feed costs an empty 304.
+pub struct FeedPoller {
+    url: String,
+    validators: Validators,
+}
+
+impl FeedPoller {
+    pub fn new(url: String) -> FeedPoller {
+        FeedPoller { url, validators: Validators::default() }
+    }
+
+    /// The feed, if it changed since the last poll.
+    pub fn poll(&mut self, transport: &dyn Transport) -> NbaResult<Option<Value>> {
+        match transport.get_json_if_modified(&self.url, &self.validators)? {
+            Conditional::Modified(body, validators) => {
+                self.validators = validators;
+                Ok(Some(body))
+            }
+            Conditional::NotModified => Ok(None),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScoreboardChange {
+    Started,
+    /// Points scored by the away and home team since the last poll
+    Score { away: i64, home: i64 },
+    Period(i64),
+    /// Tricode of the team that took the lead
+    LeadChange(String),
+    Final,
+}
+
+impl ScoreboardChange {
+    fn name(&self) -> &'static str {
+        match self {
+            ScoreboardChange::Started => "started",
+            ScoreboardChange::Score { .. } => "score",
+            ScoreboardChange::Period(_) => "period",
+            ScoreboardChange::LeadChange(_) => "lead change",
+            ScoreboardChange::Final => "final",
+        }
+    }
+
+    fn detail(&self, game: &GameSnapshot) -> String {
+        let scored = |tricode: &str, points: i64| if points == 0 { None } else { Some(format!("{} {:+}", tricode, points)) };
+        match self {
+            ScoreboardChange::Score { away, home } => [scored(&game.away.tricode, *away), scored(&game.home.tricode, *home)]
+                .iter()
+                .flatten()
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(", "),
+            ScoreboardChange::Period(period) => period_label(*period),
+            ScoreboardChange::LeadChange(tricode) => format!("{} lead", tricode),
+            ScoreboardChange::Started | ScoreboardChange::Final => String::new(),
+        }
+    }
+}
+
+/// Remembers the last scoreboard seen to tell what the next one changed.
+#[derive(Default)]
+pub struct ScoreboardWatcher {
+    games: HashMap<String, GameSnapshot>,
+    // Last team ahead in each game, a tie doesn't take the lead from anyone
+    leaders: HashMap<String, String>,
+}
+
+impl ScoreboardWatcher {
+    /// Changes in `snapshots` since the previous call, in the order they
+    /// happened within each game. A game seen for the first time has none.
+    pub fn update(&mut self, snapshots: &[GameSnapshot]) -> Vec<(GameSnapshot, ScoreboardChange)> {
+        let mut changes = Vec::new();
+        for game in snapshots {
+            let leader = match game.margin().cmp(&0) {
+                Ordering::Greater => Some(&game.home.tricode),
+                Ordering::Less => Some(&game.away.tricode),
+                Ordering::Equal => None,
+            };
+            if let Some(previous) = self.games.get(&game.game_id) {
+                let mut game_changes = Vec::new();
+                if previous.status == 1 && game.status > 1 {
+                    game_changes.push(ScoreboardChange::Started);
+                }
+                if game.period > previous.period && previous.status > 1 {
+                    game_changes.push(ScoreboardChange::Period(game.period));
+                }
+                if game.away.score != previous.away.score || game.home.score != previous.home.score {
+                    game_changes.push(ScoreboardChange::Score {
+                        away: game.away.score - previous.away.score,
+                        home: game.home.score - previous.home.score,
+                    });
+                }
+                let previous_leader = self.leaders.get(&game.game_id);
+                if let (Some(leader), Some(previous_leader)) = (leader, previous_leader) {
+                    if leader != previous_leader {
+                        game_changes.push(ScoreboardChange::LeadChange(leader.clone()));
+                    }
+                }
+                if previous.status < 3 && game.status == 3 {
+                    game_changes.push(ScoreboardChange::Final);
+                }
+                changes.extend(game_changes.into_iter().map(|change| (game.clone(), change)));
+            }
+            if let Some(leader) = leader {
+                self.leaders.insert(game.game_id.clone(), leader.clone());
+            }
+            self.games.insert(game.game_id.clone(), game.clone());
+        }
+        changes
+    }
+}
+
+/// Rows of `changes`, one per change.
+pub fn changes_table(changes: &[(GameSnapshot, ScoreboardChange)]) -> OutputTable {
+    let mut table = OutputTable::new("Changes", &["time", "game_id", "change", "detail", "score", "status"]);
+    let time = chrono::Local::now().format("%H:%M:%S").to_string();
+    for (game, change) in changes {
+        table.rows.push(vec![
+            time.clone().into(),
+            game.game_id.clone().into(),
+            change.name().into(),
+            change.detail(game).into(),
+            game.score_line().into(),
+            game.status_text.clone().into(),
+        ]);
+    }
+    table
+}
+
+/// Polls the live scoreboard every `interval`, writes it in full once and
+/// then only what changed. Returns once every game is final, or right away
+/// when the scoreboard has no games.
+pub fn watch_scoreboard(transport: &dyn Transport, interval: Duration, output: &Output) -> NbaResult<()> {
+    let mut poller = FeedPoller::new(scoreboard_url(transport));
+    let mut watcher = ScoreboardWatcher::default();
+    let mut first_poll = true;
+    loop {
+        if let Some(json) = poller.poll(transport)? {
+            let snapshots = scoreboard_snapshots(&json)?;
+            let changes = watcher.update(&snapshots);
+            if first_poll {
+                if snapshots.is_empty() {
+                    eprintln!("no games on the scoreboard");
+                    return Ok(());
+                }
+                output.write(&[live_scoreboard_from_json(&json)?])?;
+                first_poll = false;
+            } else if !changes.is_empty() {
+                output.write(&[changes_table(&changes)])?;
+            }
+            if snapshots.iter().all(|game| game.status == 3) {
+                return Ok(());
+            }
+        }
+        thread::sleep(interval);
+    }
+}
+
+/// Polls the live box score of each game every `interval` and writes a
+/// game's box score again whenever it changed. A game's feed is only
+/// published shortly before tip off, until then it's polled for. Returns
+/// once every game is final.
+pub fn watch_boxscores(transport: &dyn Transport, game_ids: &[String], interval: Duration, output: &Output) -> NbaResult<()> {
+    let mut games: Vec<(FeedPoller, bool)> = game_ids.iter()
+        .map(|game_id| (FeedPoller::new(boxscore_url(transport, game_id)), false))
+        .collect();
+    loop {
+        for (poller, is_final) in games.iter_mut().filter(|(_, is_final)| !*is_final) {
+            match poller.poll(transport) {
+                Ok(Some(json)) => {
+                    output.write(&[boxscore_from_json(&json)?])?;
+                    *is_final = boxscore_game_status(&json)? == 3;
+                }
+                Ok(None) | Err(NbaError::HttpStatus { status: 403 | 404, .. }) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        if games.iter().all(|(_, is_final)| *is_final) {
+            return Ok(());
+        }
+        thread::sleep(interval);
+    }
+}
+
+/// Polls a game's live play by play every `interval` and hands `write` the
+/// actions matching `filter` it hasn't had yet. Actions are told apart by
+/// number, anything up to the highest number seen is never written again.
+/// Returns once the game has ended.
+pub fn follow_playbyplay(transport: &dyn Transport, game_id: &str, filter: &EventFilter, interval: Duration,
+    mut write: impl FnMut(&[&PlayByPlayEvent]) -> NbaResult<()>) -> NbaResult<()> {
+    let mut poller = FeedPoller::new(playbyplay_url(transport, game_id));
+    let mut last_event_num = 0;
+    loop {
+        match poller.poll(transport) {
+            Ok(Some(json)) => {
+                // The whole game goes through the filter, margin and team
+                // conditions depend on the events before
+                let events = live_events_from_json(&json)?;
+                let new_events: Vec<&PlayByPlayEvent> = filter.apply(&events).into_iter()
+                    .filter(|event| event.event_num > last_event_num)
+                    .collect();
+                if !new_events.is_empty() {
+                    write(&new_events)?;
+                }
+                last_event_num = events.iter().map(|event| event.event_num).fold(last_event_num, i64::max);
+                if live_game_ended(&json) {
+                    return Ok(());
+                }
+            }
+            // The feed is only published once the game starts
+            Ok(None) | Err(NbaError::HttpStatus { status: 403 | 404, .. }) => {}
+            Err(e) => return Err(e),
+        }
+        thread::sleep(interval);
+    }
+}